@@ -0,0 +1,650 @@
+use ssh_encoding::Encode;
+use ssh_key::{PrivateKey, PublicKey};
+use std::env;
+use std::fmt;
+use std::io;
+#[cfg(unix)]
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_ADD_IDENTITY: u8 = 17;
+const SSH_AGENTC_REMOVE_IDENTITY: u8 = 18;
+const SSH_AGENTC_REMOVE_ALL_IDENTITIES: u8 = 19;
+const SSH_AGENTC_LOCK: u8 = 22;
+const SSH_AGENTC_UNLOCK: u8 = 23;
+const SSH_AGENTC_ADD_ID_CONSTRAINED: u8 = 25;
+
+const SSH_AGENT_CONSTRAIN_LIFETIME: u8 = 1;
+const SSH_AGENT_CONSTRAIN_CONFIRM: u8 = 2;
+
+/// Upper bound for a single agent message, same as OpenSSH.
+#[cfg(unix)]
+const MAX_AGENT_REPLY_LEN: usize = 256 * 1024;
+
+/// Errors returned by the agent client.
+#[derive(Debug)]
+pub enum AgentError {
+    /// `SSH_AUTH_SOCK` is not set.
+    NoSocket,
+    /// The agent socket cannot be used on this platform.
+    Unavailable,
+    /// Socket could not be reached or the connection broke.
+    Io(io::Error),
+    /// The agent answered with `SSH_AGENT_FAILURE`.
+    Failure,
+    /// The agent answered with a message we did not expect.
+    UnexpectedResponse(u8),
+    /// The agent reply could not be decoded.
+    Malformed,
+    /// The key could not be encoded for the agent.
+    Encoding(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::NoSocket => write!(f, "SSH_AUTH_SOCK is not set, is ssh-agent running?"),
+            AgentError::Unavailable => write!(f, "ssh-agent is unavailable on this platform"),
+            AgentError::Io(err) => write!(f, "Failed to talk to ssh-agent: {}", err),
+            AgentError::Failure => write!(f, "ssh-agent refused the request"),
+            AgentError::UnexpectedResponse(code) => {
+                write!(f, "Unexpected response from ssh-agent: {}", code)
+            }
+            AgentError::Malformed => write!(f, "Malformed response from ssh-agent"),
+            AgentError::Encoding(err) => write!(f, "Failed to encode key for ssh-agent: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl From<io::Error> for AgentError {
+    fn from(err: io::Error) -> Self {
+        AgentError::Io(err)
+    }
+}
+
+/// An identity held by the agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// Public key blob in SSH wire format.
+    pub key_blob: Vec<u8>,
+    /// Comment the key was added with.
+    pub comment: String,
+}
+
+impl Identity {
+    /// Decodes the key blob, if it is a key type we understand.
+    pub fn public_key(&self) -> Option<PublicKey> {
+        let mut public_key = PublicKey::from_bytes(&self.key_blob).ok()?;
+        public_key.set_comment(self.comment.as_str());
+        Some(public_key)
+    }
+}
+
+/// Constraints applied to an identity when it is added, like `ssh-add -t` and `-c`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// Lifetime in seconds after which the agent drops the key.
+    pub lifetime: Option<u32>,
+    /// Ask for confirmation every time the key is used.
+    pub confirm: bool,
+}
+
+impl Constraints {
+    /// Returns whether no constraint is set.
+    pub fn is_empty(&self) -> bool {
+        self.lifetime.is_none() && !self.confirm
+    }
+}
+
+/// Client for the ssh-agent protocol over a Unix socket.
+#[derive(Clone, Debug)]
+pub struct AgentClient {
+    socket_path: PathBuf,
+}
+
+impl AgentClient {
+    /// Constructs a new instance of [`AgentClient`] for the given socket.
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+        }
+    }
+
+    /// Constructs a client from `SSH_AUTH_SOCK`.
+    pub fn from_env() -> Result<Self, AgentError> {
+        match env::var_os("SSH_AUTH_SOCK") {
+            Some(path) if !path.is_empty() => Ok(Self::new(path)),
+            _ => Err(AgentError::NoSocket),
+        }
+    }
+
+    /// Path of the agent socket.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Lists every identity the agent holds.
+    pub fn list_identities(&self) -> Result<Vec<Identity>, AgentError> {
+        let reply = self.request(&[SSH_AGENTC_REQUEST_IDENTITIES])?;
+        let mut reader = WireReader::new(&reply);
+        match reader.read_u8()? {
+            SSH_AGENT_IDENTITIES_ANSWER => {}
+            SSH_AGENT_FAILURE => return Err(AgentError::Failure),
+            code => return Err(AgentError::UnexpectedResponse(code)),
+        }
+
+        let count = reader.read_u32()? as usize;
+        let mut identities = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let key_blob = reader.read_string()?.to_vec();
+            let comment = String::from_utf8_lossy(reader.read_string()?).into_owned();
+            identities.push(Identity { key_blob, comment });
+        }
+        Ok(identities)
+    }
+
+    /// Returns whether the agent holds the given public key.
+    pub fn contains(&self, public_key: &PublicKey) -> Result<bool, AgentError> {
+        let key_blob = public_key_blob(public_key)?;
+        Ok(self
+            .list_identities()?
+            .iter()
+            .any(|identity| identity.key_blob == key_blob))
+    }
+
+    /// Adds a decrypted private key to the agent.
+    pub fn add_identity(&self, private_key: &PrivateKey) -> Result<(), AgentError> {
+        self.add_identity_constrained(private_key, &Constraints::default())
+    }
+
+    /// Adds a decrypted private key to the agent with the given constraints.
+    pub fn add_identity_constrained(
+        &self,
+        private_key: &PrivateKey,
+        constraints: &Constraints,
+    ) -> Result<(), AgentError> {
+        let message_type = if constraints.is_empty() {
+            SSH_AGENTC_ADD_IDENTITY
+        } else {
+            SSH_AGENTC_ADD_ID_CONSTRAINED
+        };
+        let mut message = vec![message_type];
+        private_key
+            .key_data()
+            .encode(&mut message)
+            .map_err(|err| AgentError::Encoding(err.to_string()))?;
+        put_string(&mut message, private_key.comment().as_bytes());
+        if let Some(lifetime) = constraints.lifetime {
+            message.push(SSH_AGENT_CONSTRAIN_LIFETIME);
+            message.extend_from_slice(&lifetime.to_be_bytes());
+        }
+        if constraints.confirm {
+            message.push(SSH_AGENT_CONSTRAIN_CONFIRM);
+        }
+        self.request_success(&message)
+    }
+
+    /// Removes a single identity, identified by its public key blob.
+    pub fn remove_identity(&self, key_blob: &[u8]) -> Result<(), AgentError> {
+        let mut message = vec![SSH_AGENTC_REMOVE_IDENTITY];
+        put_string(&mut message, key_blob);
+        self.request_success(&message)
+    }
+
+    /// Removes every identity from the agent.
+    pub fn remove_all_identities(&self) -> Result<(), AgentError> {
+        self.request_success(&[SSH_AGENTC_REMOVE_ALL_IDENTITIES])
+    }
+
+    /// Locks the agent with a password, like `ssh-add -x`.
+    pub fn lock(&self, password: &str) -> Result<(), AgentError> {
+        let mut message = vec![SSH_AGENTC_LOCK];
+        put_string(&mut message, password.as_bytes());
+        self.request_success(&message)
+    }
+
+    /// Unlocks the agent, like `ssh-add -X`.
+    pub fn unlock(&self, password: &str) -> Result<(), AgentError> {
+        let mut message = vec![SSH_AGENTC_UNLOCK];
+        put_string(&mut message, password.as_bytes());
+        self.request_success(&message)
+    }
+
+    fn request_success(&self, message: &[u8]) -> Result<(), AgentError> {
+        let reply = self.request(message)?;
+        match reply.first() {
+            Some(&SSH_AGENT_SUCCESS) => Ok(()),
+            Some(&SSH_AGENT_FAILURE) => Err(AgentError::Failure),
+            Some(&code) => Err(AgentError::UnexpectedResponse(code)),
+            None => Err(AgentError::Malformed),
+        }
+    }
+
+    #[cfg(unix)]
+    fn request(&self, message: &[u8]) -> Result<Vec<u8>, AgentError> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+
+        let mut frame = Vec::with_capacity(message.len() + 4);
+        put_string(&mut frame, message);
+        stream.write_all(&frame)?;
+
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_AGENT_REPLY_LEN {
+            return Err(AgentError::Malformed);
+        }
+
+        let mut reply = vec![0u8; len];
+        stream.read_exact(&mut reply)?;
+        Ok(reply)
+    }
+
+    #[cfg(not(unix))]
+    fn request(&self, _message: &[u8]) -> Result<Vec<u8>, AgentError> {
+        Err(AgentError::Unavailable)
+    }
+}
+
+/// Encodes a public key into the blob format the agent uses to identify it.
+pub fn public_key_blob(public_key: &PublicKey) -> Result<Vec<u8>, AgentError> {
+    public_key
+        .to_bytes()
+        .map_err(|err| AgentError::Encoding(err.to_string()))
+}
+
+/// Parses a lifetime the way `ssh-add -t` does, e.g. `600`, `90m` or `1h30m`.
+pub fn parse_lifetime(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let seconds = number.parse::<u64>().ok()?.checked_mul(multiplier)?;
+        total = total.checked_add(seconds)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        total = total.checked_add(number.parse().ok()?)?;
+    }
+
+    u32::try_from(total).ok().filter(|&total| total > 0)
+}
+
+fn put_string(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+struct WireReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> WireReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AgentError> {
+        if self.data.len() < len {
+            return Err(AgentError::Malformed);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, AgentError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, AgentError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_string(&mut self) -> Result<&'a [u8], AgentError> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use ssh_encoding::Decode;
+    use ssh_key::private::KeypairData;
+    use ssh_key::public::KeyData;
+    use ssh_key::rand_core::OsRng;
+    use ssh_key::Algorithm;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// What the mock agent holds, in the order identities were added.
+    #[derive(Default)]
+    struct MockState {
+        identities: Vec<(Identity, Constraints)>,
+        /// Message types of the add requests it received.
+        add_requests: Vec<u8>,
+        password: Option<String>,
+    }
+
+    /// An agent listening on a socket in a temporary directory, answering
+    /// one request per connection like [`AgentClient`] sends them.
+    struct MockAgent {
+        dir: PathBuf,
+        client: AgentClient,
+        state: Arc<Mutex<MockState>>,
+    }
+
+    impl MockAgent {
+        fn start(name: &str) -> Self {
+            let dir =
+                env::temp_dir().join(format!("lazyssh-agent-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let socket_path = dir.join("agent.sock");
+            let listener = UnixListener::bind(&socket_path).unwrap();
+
+            let state = Arc::new(Mutex::new(MockState::default()));
+            let agent_state = Arc::clone(&state);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else {
+                        break;
+                    };
+                    serve(&mut stream, &agent_state);
+                }
+            });
+
+            Self {
+                dir,
+                client: AgentClient::new(socket_path),
+                state,
+            }
+        }
+
+        fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+            self.state.lock().unwrap()
+        }
+    }
+
+    impl Drop for MockAgent {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn serve(stream: &mut UnixStream, state: &Mutex<MockState>) {
+        let mut len = [0u8; 4];
+        if stream.read_exact(&mut len).is_err() {
+            return;
+        }
+        let mut message = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut message).unwrap();
+        let reply = handle(&message, &mut state.lock().unwrap())
+            .unwrap_or_else(|_| vec![SSH_AGENT_FAILURE]);
+        let mut frame = Vec::new();
+        put_string(&mut frame, &reply);
+        stream.write_all(&frame).unwrap();
+    }
+
+    fn handle(message: &[u8], state: &mut MockState) -> Result<Vec<u8>, AgentError> {
+        let mut reader = WireReader::new(message);
+        let code = reader.read_u8()?;
+        let locked = state.password.is_some();
+        match code {
+            SSH_AGENTC_REQUEST_IDENTITIES => {
+                // A locked agent pretends to hold nothing, like OpenSSH does.
+                let identities = if locked {
+                    &[][..]
+                } else {
+                    &state.identities[..]
+                };
+                let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+                reply.extend_from_slice(&(identities.len() as u32).to_be_bytes());
+                for (identity, _) in identities {
+                    put_string(&mut reply, &identity.key_blob);
+                    put_string(&mut reply, identity.comment.as_bytes());
+                }
+                Ok(reply)
+            }
+            SSH_AGENTC_ADD_IDENTITY | SSH_AGENTC_ADD_ID_CONSTRAINED if !locked => {
+                let mut data = reader.data;
+                let keypair = KeypairData::decode(&mut data).map_err(|_| AgentError::Malformed)?;
+                let key_data = KeyData::try_from(&keypair).map_err(|_| AgentError::Malformed)?;
+                let key_blob = PublicKey::new(key_data, "").to_bytes().unwrap();
+
+                let mut reader = WireReader::new(data);
+                let comment = String::from_utf8_lossy(reader.read_string()?).into_owned();
+                let mut constraints = Constraints::default();
+                while !reader.data.is_empty() {
+                    match reader.read_u8()? {
+                        SSH_AGENT_CONSTRAIN_LIFETIME => {
+                            constraints.lifetime = Some(reader.read_u32()?)
+                        }
+                        SSH_AGENT_CONSTRAIN_CONFIRM => constraints.confirm = true,
+                        _ => return Err(AgentError::Failure),
+                    }
+                }
+
+                state.add_requests.push(code);
+                state
+                    .identities
+                    .retain(|(identity, _)| identity.key_blob != key_blob);
+                state
+                    .identities
+                    .push((Identity { key_blob, comment }, constraints));
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            SSH_AGENTC_REMOVE_IDENTITY if !locked => {
+                let key_blob = reader.read_string()?;
+                let count = state.identities.len();
+                state
+                    .identities
+                    .retain(|(identity, _)| identity.key_blob != key_blob);
+                if state.identities.len() == count {
+                    return Err(AgentError::Failure);
+                }
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            SSH_AGENTC_REMOVE_ALL_IDENTITIES if !locked => {
+                state.identities.clear();
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            SSH_AGENTC_LOCK if !locked => {
+                let password = String::from_utf8_lossy(reader.read_string()?).into_owned();
+                state.password = Some(password);
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            SSH_AGENTC_UNLOCK if locked => {
+                let password = reader.read_string()?;
+                if state.password.as_deref().map(str::as_bytes) != Some(password) {
+                    return Err(AgentError::Failure);
+                }
+                state.password = None;
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            _ => Err(AgentError::Failure),
+        }
+    }
+
+    fn private_key(comment: &str) -> PrivateKey {
+        let mut private_key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        private_key.set_comment(comment);
+        private_key
+    }
+
+    #[test]
+    fn lists_adds_and_removes_identities() {
+        let agent = MockAgent::start("identities");
+        assert_eq!(agent.client.list_identities().unwrap(), Vec::new());
+
+        let alice = private_key("alice@laptop");
+        let bob = private_key("bob@desktop");
+        agent.client.add_identity(&alice).unwrap();
+        agent.client.add_identity(&bob).unwrap();
+        assert_eq!(
+            agent.state().add_requests,
+            [SSH_AGENTC_ADD_IDENTITY, SSH_AGENTC_ADD_IDENTITY]
+        );
+
+        let identities = agent.client.list_identities().unwrap();
+        let comments: Vec<_> = identities
+            .iter()
+            .map(|identity| identity.comment.as_str())
+            .collect();
+        assert_eq!(comments, ["alice@laptop", "bob@desktop"]);
+        assert_eq!(identities[0].public_key().unwrap(), *alice.public_key());
+        assert!(agent.client.contains(alice.public_key()).unwrap());
+
+        let alice_blob = public_key_blob(alice.public_key()).unwrap();
+        agent.client.remove_identity(&alice_blob).unwrap();
+        assert!(!agent.client.contains(alice.public_key()).unwrap());
+        assert!(agent.client.contains(bob.public_key()).unwrap());
+        assert!(matches!(
+            agent.client.remove_identity(&alice_blob),
+            Err(AgentError::Failure)
+        ));
+
+        agent.client.remove_all_identities().unwrap();
+        assert_eq!(agent.client.list_identities().unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn adds_identities_with_constraints() {
+        let agent = MockAgent::start("constraints");
+        let constraints = Constraints {
+            lifetime: Some(600),
+            confirm: true,
+        };
+        agent
+            .client
+            .add_identity_constrained(&private_key("alice@laptop"), &constraints)
+            .unwrap();
+        agent
+            .client
+            .add_identity_constrained(
+                &private_key("bob@desktop"),
+                &Constraints {
+                    lifetime: None,
+                    confirm: true,
+                },
+            )
+            .unwrap();
+
+        let state = agent.state();
+        assert_eq!(
+            state.add_requests,
+            [SSH_AGENTC_ADD_ID_CONSTRAINED, SSH_AGENTC_ADD_ID_CONSTRAINED]
+        );
+        assert_eq!(state.identities[0].1, constraints);
+        assert_eq!(state.identities[1].1.lifetime, None);
+        assert!(state.identities[1].1.confirm);
+    }
+
+    #[test]
+    fn locks_and_unlocks() {
+        let agent = MockAgent::start("lock");
+        agent
+            .client
+            .add_identity(&private_key("alice@laptop"))
+            .unwrap();
+
+        agent.client.lock("secret").unwrap();
+        assert_eq!(agent.client.list_identities().unwrap(), Vec::new());
+        assert!(matches!(
+            agent.client.add_identity(&private_key("bob@desktop")),
+            Err(AgentError::Failure)
+        ));
+        assert!(matches!(
+            agent.client.lock("secret"),
+            Err(AgentError::Failure)
+        ));
+        assert!(matches!(
+            agent.client.unlock("wrong"),
+            Err(AgentError::Failure)
+        ));
+
+        agent.client.unlock("secret").unwrap();
+        assert_eq!(agent.client.list_identities().unwrap().len(), 1);
+        assert!(matches!(
+            agent.client.unlock("secret"),
+            Err(AgentError::Failure)
+        ));
+    }
+
+    #[test]
+    fn fails_without_a_listening_agent() {
+        let agent = MockAgent::start("missing");
+        let client = AgentClient::new(agent.dir.join("missing.sock"));
+        assert!(matches!(client.list_identities(), Err(AgentError::Io(_))));
+    }
+
+    #[test]
+    fn parses_lifetimes() {
+        assert_eq!(parse_lifetime("600"), Some(600));
+        assert_eq!(parse_lifetime(" 90m "), Some(90 * 60));
+        assert_eq!(parse_lifetime("1h30m"), Some(90 * 60));
+        assert_eq!(parse_lifetime("1H30S"), Some(60 * 60 + 30));
+        assert_eq!(parse_lifetime("2d"), Some(2 * 24 * 60 * 60));
+        assert_eq!(parse_lifetime("1w"), Some(7 * 24 * 60 * 60));
+        assert_eq!(parse_lifetime("5m10"), Some(5 * 60 + 10));
+
+        assert_eq!(parse_lifetime(""), None);
+        assert_eq!(parse_lifetime("0"), None);
+        assert_eq!(parse_lifetime("m"), None);
+        assert_eq!(parse_lifetime("10x"), None);
+        assert_eq!(parse_lifetime("-5"), None);
+        assert_eq!(parse_lifetime("10000w"), None);
+        // Totals that overflow are rejected rather than wrapped.
+        assert_eq!(parse_lifetime("3000000000000000w"), None);
+        assert_eq!(parse_lifetime("18446744073709551615s1"), None);
+        assert_eq!(parse_lifetime("99999999999999999999"), None);
+    }
+
+    #[test]
+    fn rejects_truncated_wire_data() {
+        assert!(matches!(
+            WireReader::new(&[]).read_u8(),
+            Err(AgentError::Malformed)
+        ));
+        assert!(matches!(
+            WireReader::new(&[0, 0, 1]).read_u32(),
+            Err(AgentError::Malformed)
+        ));
+        assert!(matches!(
+            WireReader::new(&[0, 0, 0, 5, b'a', b'b']).read_string(),
+            Err(AgentError::Malformed)
+        ));
+
+        let mut reader = WireReader::new(&[0, 0, 0, 2, b'o', b'k', 0, 0]);
+        assert_eq!(reader.read_string().unwrap(), b"ok");
+        assert!(matches!(reader.read_u32(), Err(AgentError::Malformed)));
+    }
+}
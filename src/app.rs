@@ -1,7 +1,9 @@
-use arboard::Clipboard;
 use color_eyre::Result;
-use dirs;
-use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::crossterm::event::{
+    DisableBracketedPaste, EnableBracketedPaste, KeyCode, KeyEvent, KeyModifiers,
+};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen};
 use ratatui::style::Stylize;
 use ratatui::widgets::{
     Clear, List, ListItem, ListState, Padding, Scrollbar, ScrollbarOrientation, ScrollbarState,
@@ -10,21 +12,54 @@ use ratatui::widgets::{
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
-    text::Line,
+    text::{Line, Span, Text},
     widgets::{Block, BorderType, Paragraph},
     DefaultTerminal, Frame,
 };
-use std::collections::HashSet;
-use std::fs;
-use std::fs::read_to_string;
+use ssh_key::PublicKey;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
 use std::iter::FromIterator;
-use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
-use trash::delete;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_width::UnicodeWidthStr;
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::agent::{self, AgentError, Constraints, Identity};
+use crate::authorized_keys::{self, AuthorizedKey, AuthorizedKeysFile, KeyOption};
+use crate::backend::{self, Filesystem, SshBackend, WatchGuard};
 use crate::event::{EventHandler, TerminalEvent};
+use crate::input::TextInput;
+use crate::inventory::{KeyFile, KeyInventory};
+use crate::jobs::{
+    AgentAddOutcome, AgentLockOutcome, JobId, JobOutput, JobResult, Jobs, PassphraseOutcome,
+};
+use crate::keys::{self, FingerprintHash, KeygenError};
+use crate::known_hosts::{HostPatterns, KnownHostEntry, KnownHostsFile, Marker};
+use crate::ssh_config::{self, BlockKind, ConfigDocument, HostEntry, SshConfig};
+use crate::ssh_files::{FileKind, PrivateKeyInfo};
 
-const FORM_FIELD_COUNT: usize = 6;
+const FORM_FIELD_COUNT: usize = 10;
+const AGENT_ADD_FORM_FIELD_COUNT: usize = 3;
+const HOST_FORM_FIELD_COUNT: usize = 7;
+const HOST_IDENTITY_NONE: &str = "(none)";
+const AUTHORIZED_KEY_FORM_FIELD_COUNT: usize = 5;
+const PASSPHRASE_FORM_FIELD_COUNT: usize = 3;
+/// Smallest terminal the layout is drawn in; below it a notice is shown instead.
+const MIN_TERMINAL_WIDTH: u16 = 80;
+const MIN_TERMINAL_HEIGHT: u16 = 24;
+/// How often the screen is redrawn to animate the spinner while jobs run.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+const CREATE_KEY_JOB: &str = "Creating SSH key";
+const AGENT_ADD_JOB: &str = "Adding SSH key to agent";
+const AGENT_LIST_JOB: &str = "Listing SSH agent keys";
+const AGENT_REMOVE_JOB: &str = "Removing SSH key from agent";
+const AGENT_REMOVE_ALL_JOB: &str = "Removing all SSH keys from agent";
+const AGENT_LOCK_JOB: &str = "Locking SSH agent";
+const AGENT_UNLOCK_JOB: &str = "Unlocking SSH agent";
+const CHANGE_PASSPHRASE_JOB: &str = "Changing passphrase";
 
 struct KeyBindingItem {
     keycode: char,
@@ -56,47 +91,199 @@ impl FromIterator<(char, &'static str)> for KeyBindings {
     }
 }
 
+struct AgentIdentityItem {
+    identity: Identity,
+    key_type: &'static str,
+    bits: Option<u32>,
+    fingerprint: String,
+    file: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Files,
+    Hosts,
+}
+
+/// Outcome of the last listing of the agent identities.
+enum AgentListing {
+    /// Not listed yet.
+    Pending,
+    Listed,
+    Failed(String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AgentLockAction {
+    Lock,
+    Unlock,
+}
+
+/// A message shown in the title of a form field.
+enum FieldMessage {
+    /// Blocks submission.
+    Error(String),
+    /// Advice that does not block submission.
+    Hint(String),
+}
+
+struct AgentKeyConstraints {
+    expires_at: Option<SystemTime>,
+    confirm: bool,
+}
+
+/// A security key pair being generated by an interactive `ssh-keygen`, which
+/// needs the terminal for touch and PIN prompts.
+struct PendingSecurityKeygen {
+    args: Vec<String>,
+    temp_path: PathBuf,
+    key_path: PathBuf,
+}
+
 pub struct App {
     running: bool,
     command_log: Vec<String>,
 
     event_handler: EventHandler,
+    backend: Arc<dyn SshBackend>,
+    jobs: Jobs,
+    inventory: KeyInventory,
+    /// Keeps `~/.ssh` watched while the app runs.
+    ssh_dir_watch: Option<WatchGuard>,
+
+    focused_pane: Pane,
 
     ssh_files: Vec<String>,
     ssh_files_state: ListState,
 
+    ssh_config: SshConfig,
+    ssh_hosts: Vec<String>,
+    ssh_hosts_state: ListState,
+
     show_key_bindings: bool,
     show_confirm_delete: bool,
     show_create_form: bool,
+    show_agent_panel: bool,
+    show_key_details: bool,
+    show_agent_add_form: bool,
+    show_agent_passphrase_prompt: bool,
+    show_agent_lock_prompt: bool,
+    show_host_form: bool,
+    show_connect_prompt: bool,
+    show_known_hosts_panel: bool,
+    show_authorized_keys_panel: bool,
+    show_authorized_key_form: bool,
+    show_passphrase_form: bool,
+    show_overwrite_key_confirm: bool,
+    show_remove_all_agent_confirm: bool,
+    create_form_submitted: bool,
+
+    agent_identities: Vec<AgentIdentityItem>,
+    agent_identities_state: ListState,
+    agent_key_constraints: HashMap<Vec<u8>, AgentKeyConstraints>,
+
+    agent_add_form_state: ListState,
+    lifetime_options: Vec<&'static str>,
+    selected_lifetime_index: usize,
+    custom_lifetime: TextInput,
+    confirm_options: Vec<&'static str>,
+    selected_confirm_index: usize,
+
+    agent_passphrase: TextInput,
+    pending_agent_add: Option<(PathBuf, Constraints)>,
+
+    agent_locked: bool,
+    agent_listing: AgentListing,
+    /// The listing whose result is still wanted.
+    agent_listing_job: Option<JobId>,
+    agent_lock_action: AgentLockAction,
+    agent_lock_form_state: ListState,
+    agent_lock_password: TextInput,
+    agent_lock_password_confirm: TextInput,
+
+    host_form_state: ListState,
+    host_form_original_alias: Option<String>,
+    host_alias: TextInput,
+    host_hostname: TextInput,
+    host_user: TextInput,
+    host_port: TextInput,
+    host_identity_options: Vec<String>,
+    selected_host_identity_index: usize,
+    host_proxy_jump: TextInput,
+    host_extra_options: TextInput,
+
+    connect_destination: TextInput,
+    pending_ssh_session: Option<Vec<String>>,
+    pending_security_keygen: Option<PendingSecurityKeygen>,
+
+    known_hosts: Option<KnownHostsFile>,
+    known_hosts_state: ListState,
+    known_hosts_search: TextInput,
+    known_hosts_searching: bool,
+
+    authorized_keys: Option<AuthorizedKeysFile>,
+    authorized_keys_state: ListState,
+    authorized_key_form_state: ListState,
+    authorized_key_from: TextInput,
+    authorized_key_command: TextInput,
+    authorized_key_expiry_time: TextInput,
+    selected_authorized_key_restrict_index: usize,
+    authorized_key_other_options: TextInput,
+
+    passphrase_form_state: ListState,
+    passphrase_form_key: Option<PathBuf>,
+    old_passphrase: TextInput,
+    new_passphrase: TextInput,
+    new_re_passphrase: TextInput,
 
     create_form_state: ListState,
-    key_name: String,
+    key_name: TextInput,
     key_type: String,
     key_bits: String,
-    passphrase: String,
-    re_passphrase: String,
+    passphrase: TextInput,
+    re_passphrase: TextInput,
     key_types: Vec<&'static str>,
     selected_key_type_index: usize,
     bits_options: Vec<&'static str>,
     selected_bits_index: usize,
-    comment: String,
+    selected_sk_resident_index: usize,
+    selected_sk_verify_required_index: usize,
+    sk_application: TextInput,
+    sk_user: TextInput,
+    comment: TextInput,
 
     key_bindings: KeyBindings,
+    fingerprint_hash: FingerprintHash,
+    /// Private key file whose raw content is shown; reset when the selection moves.
+    revealed_file: Option<String>,
 }
 
 impl App {
-    pub fn new(event_handler: EventHandler) -> Self {
+    pub fn new(event_handler: EventHandler, backend: Arc<dyn SshBackend>) -> Self {
         let mut ssh_files_state = ListState::default();
         ssh_files_state.select(Some(0));
         let mut create_form_state = ListState::default();
         create_form_state.select(Some(0));
+        let mut agent_add_form_state = ListState::default();
+        agent_add_form_state.select(Some(0));
+        let jobs = Jobs::new(event_handler.sender.clone());
         Self {
             running: true,
 
+            focused_pane: Pane::Files,
+
             ssh_files: Vec::new(),
             ssh_files_state,
 
+            ssh_config: SshConfig::default(),
+            ssh_hosts: Vec::new(),
+            ssh_hosts_state: ListState::default(),
+
             event_handler,
+            backend,
+            jobs,
+            inventory: KeyInventory::default(),
+            ssh_dir_watch: None,
 
             show_confirm_delete: false,
             command_log: Vec::new(),
@@ -105,22 +292,113 @@ impl App {
             key_bindings: KeyBindings::from_iter([
                 ('n', "Create a SSH key"),
                 ('a', "Add a SSH key to agent"),
+                ('A', "Add a SSH key to agent with lifetime/confirm"),
                 ('d', "Delete a SSH key"),
                 ('c', "Copy a SSH public key to clipboard"),
                 ('r', "Remove a SSH key from agent"),
+                ('i', "Show SSH agent identities"),
+                ('f', "Show fingerprints and randomart of a SSH key"),
+                ('v', "Reveal or hide the content of a private key"),
+                ('s', "Connect to user@host with ssh"),
+                ('k', "Manage known hosts"),
+                ('u', "Manage authorized keys"),
+                ('U', "Authorize the selected public key"),
+                ('p', "Change the passphrase of a SSH key"),
+                ('x', "Lock the SSH agent"),
+                ('X', "Unlock the SSH agent"),
             ]),
 
             show_create_form: false,
-            key_name: String::new(),
+            show_agent_panel: false,
+            show_key_details: false,
+            fingerprint_hash: FingerprintHash::default(),
+            revealed_file: None,
+            show_agent_add_form: false,
+            show_agent_passphrase_prompt: false,
+            show_agent_lock_prompt: false,
+            show_host_form: false,
+            show_connect_prompt: false,
+            show_known_hosts_panel: false,
+            show_authorized_keys_panel: false,
+            show_authorized_key_form: false,
+            show_passphrase_form: false,
+            show_overwrite_key_confirm: false,
+            show_remove_all_agent_confirm: false,
+            create_form_submitted: false,
+
+            agent_identities: Vec::new(),
+            agent_identities_state: ListState::default(),
+            agent_key_constraints: HashMap::new(),
+
+            agent_add_form_state,
+            lifetime_options: vec![
+                "none", "5m", "15m", "30m", "1h", "4h", "8h", "24h", "custom",
+            ],
+            selected_lifetime_index: 0,
+            custom_lifetime: TextInput::new(),
+            confirm_options: vec!["no", "yes"],
+            selected_confirm_index: 0,
+
+            agent_passphrase: TextInput::secret(),
+            pending_agent_add: None,
+
+            agent_locked: false,
+            agent_listing: AgentListing::Pending,
+            agent_listing_job: None,
+            agent_lock_action: AgentLockAction::Lock,
+            agent_lock_form_state: ListState::default(),
+            agent_lock_password: TextInput::secret(),
+            agent_lock_password_confirm: TextInput::secret(),
+
+            host_form_state: ListState::default(),
+            host_form_original_alias: None,
+            host_alias: TextInput::new(),
+            host_hostname: TextInput::new(),
+            host_user: TextInput::new(),
+            host_port: TextInput::new(),
+            host_identity_options: Vec::new(),
+            selected_host_identity_index: 0,
+            host_proxy_jump: TextInput::new(),
+            host_extra_options: TextInput::multiline(),
+
+            connect_destination: TextInput::new(),
+            pending_ssh_session: None,
+            pending_security_keygen: None,
+
+            known_hosts: None,
+            known_hosts_state: ListState::default(),
+            known_hosts_search: TextInput::new(),
+            known_hosts_searching: false,
+
+            authorized_keys: None,
+            authorized_keys_state: ListState::default(),
+            authorized_key_form_state: ListState::default(),
+            authorized_key_from: TextInput::new(),
+            authorized_key_command: TextInput::new(),
+            authorized_key_expiry_time: TextInput::new(),
+            selected_authorized_key_restrict_index: 0,
+            authorized_key_other_options: TextInput::new(),
+
+            passphrase_form_state: ListState::default(),
+            passphrase_form_key: None,
+            old_passphrase: TextInput::secret(),
+            new_passphrase: TextInput::secret(),
+            new_re_passphrase: TextInput::secret(),
+
+            key_name: TextInput::new(),
             key_type: String::new(),
             key_bits: String::new(),
-            passphrase: String::new(),
-            re_passphrase: String::new(),
-            key_types: vec!["rsa", "dsa", "ecdsa", "ed25519"],
+            passphrase: TextInput::secret(),
+            re_passphrase: TextInput::secret(),
+            key_types: vec!["rsa", "dsa", "ecdsa", "ed25519", "ed25519-sk", "ecdsa-sk"],
             selected_key_type_index: 0,
-            bits_options: vec!["1024", "2048", "4096"],
-            selected_bits_index: 1,
-            comment: String::new(),
+            bits_options: keys::key_bits_options("rsa").to_vec(),
+            selected_bits_index: 0,
+            selected_sk_resident_index: 0,
+            selected_sk_verify_required_index: 0,
+            sk_application: TextInput::new(),
+            sk_user: TextInput::new(),
+            comment: TextInput::new(),
             create_form_state,
         }
     }
@@ -128,33 +406,180 @@ impl App {
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         self.running = true;
         self.ssh_files = self.load_ssh_files();
+        self.load_ssh_hosts();
+        self.watch_ssh_dir();
+        self.refresh_agent_identities();
         while self.running {
             terminal.draw(|frame| self.draw(frame))?;
-            let event = self.event_handler.next()?;
+            let event = if self.jobs.is_busy() {
+                self.event_handler.next_timeout(SPINNER_INTERVAL)?
+            } else {
+                Some(self.event_handler.next()?)
+            };
             match event {
-                TerminalEvent::Tick => {}
-                TerminalEvent::Key(key_event) => {
-                    self.on_key_event(key_event);
+                Some(event) => self.on_event(event),
+                None => self.jobs.tick(),
+            }
+
+            if let Some(args) = self.pending_ssh_session.take() {
+                self.run_ssh_session(&mut terminal, args)?;
+            }
+
+            if let Some(keygen) = self.pending_security_keygen.take() {
+                self.run_security_keygen(&mut terminal, keygen)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: TerminalEvent) {
+        match event {
+            TerminalEvent::Tick => {
+                if self.agent_listing_job.is_none() {
+                    self.refresh_agent_identities();
+                }
+            }
+            TerminalEvent::Key(key_event) => {
+                self.on_key_event(key_event);
+            }
+            TerminalEvent::Mouse(_) => {}
+            TerminalEvent::Resize(_, _) => {}
+            TerminalEvent::Paste(text) => self.on_paste(&text),
+            TerminalEvent::Job(result) => self.on_job_finished(result),
+            TerminalEvent::FilesChanged => self.reload_ssh_files(),
+        }
+    }
+
+    fn on_job_finished(&mut self, result: JobResult) {
+        self.jobs.finish(result.id);
+        match result.output {
+            JobOutput::KeyCreated {
+                key_path,
+                log,
+                result,
+            } => {
+                self.command_log.extend(log);
+                self.finish_create_ssh_key(&key_path, result);
+            }
+            JobOutput::AgentAdd {
+                path,
+                constraints,
+                outcome,
+            } => self.finish_add_ssh_key_to_agent(&path, constraints, outcome),
+            JobOutput::AgentRemove { label, result } => {
+                self.finish_remove_from_agent(&label, result)
+            }
+            JobOutput::AgentRemoveAll(result) => self.finish_remove_all_agent_identities(result),
+            JobOutput::AgentLock(outcome) => self.finish_agent_lock(outcome),
+            JobOutput::AgentIdentities(identities) => {
+                if self.agent_listing_job == Some(result.id) {
+                    self.agent_listing_job = None;
+                    self.set_agent_identities(identities);
                 }
-                TerminalEvent::Mouse(_) => {}
-                TerminalEvent::Resize(_, _) => {}
             }
+            JobOutput::PassphraseChanged { path, outcome } => {
+                self.finish_change_passphrase(&path, outcome)
+            }
+        }
+    }
+
+    /// Hands the terminal to an interactive `ssh` and takes it back once the
+    /// session ends.
+    fn run_ssh_session(&mut self, terminal: &mut DefaultTerminal, args: Vec<String>) -> Result<()> {
+        let command_line = format!("ssh {}", args.join(" "));
+        let status = self.run_interactive(terminal, &command_line, "ssh", &args)?;
+
+        match status {
+            Ok(status) => match status.code() {
+                Some(code) => self.command_log.push(format!(
+                    "{} -> Session ended with exit status {}",
+                    command_line, code
+                )),
+                None => self.command_log.push(format!(
+                    "{} -> Session terminated by a signal",
+                    command_line
+                )),
+            },
+            Err(err) => self
+                .command_log
+                .push(format!("{} -> Failed to run ssh: {}", command_line, err)),
         }
         Ok(())
     }
 
+    /// Runs a command on the real terminal, suspending the UI until it exits.
+    fn run_interactive(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        command_line: &str,
+        program: &str,
+        args: &[String],
+    ) -> Result<io::Result<ExitStatus>> {
+        self.event_handler.pause();
+        let result = (|| {
+            execute!(io::stdout(), DisableBracketedPaste)?;
+            ratatui::restore();
+            println!("lazyssh: {}", command_line);
+            let status = self.backend.run_interactive(program, args);
+
+            terminal::enable_raw_mode()?;
+            execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
+            terminal.clear()?;
+            Ok(status)
+        })();
+        // Resumed on errors too, or the input thread would stay parked.
+        self.event_handler.resume();
+        result
+    }
+
+    fn run_security_keygen(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        keygen: PendingSecurityKeygen,
+    ) -> Result<()> {
+        let command_line = format!("ssh-keygen {}", shell_join(&keygen.args));
+        let status = self.run_interactive(terminal, &command_line, "ssh-keygen", &keygen.args)?;
+
+        self.command_log.push(command_line);
+        let generated = match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format!("ssh-keygen exited with {}", status)),
+            Err(err) => Err(format!("Failed to execute ssh-keygen: {}", err)),
+        };
+        let result = finish_ssh_keygen(
+            &*self.backend,
+            &keygen.temp_path,
+            &keygen.key_path,
+            self.passphrase.value(),
+            generated,
+            &mut self.command_log,
+        );
+        self.finish_create_ssh_key(&keygen.key_path, result);
+        Ok(())
+    }
+
     fn draw(&mut self, frame: &mut Frame) {
         let area = frame.area().inner(Margin {
             vertical: 0,
             horizontal: 0,
         });
+        if area.width < MIN_TERMINAL_WIDTH || area.height < MIN_TERMINAL_HEIGHT {
+            self.render_terminal_too_small(frame, area);
+            return;
+        }
+        self.refresh_inventory();
 
         let main_chunks = self.create_main_layout(area);
         let content_chunks = self.create_content_layout(main_chunks[0]);
+        let left_chunks = self.create_left_layout(content_chunks[0]);
         let right_chunks = self.create_right_layout(content_chunks[1]);
 
-        self.render_ssh_files(frame, content_chunks[0]);
-        self.render_ssh_content(frame, right_chunks[0]);
+        self.render_ssh_files(frame, left_chunks[0]);
+        self.render_ssh_hosts(frame, left_chunks[1]);
+        match self.focused_pane {
+            Pane::Files => self.render_ssh_content(frame, right_chunks[0]),
+            Pane::Hosts => self.render_host_settings(frame, right_chunks[0]),
+        }
         self.render_ssh_agent_status(frame, right_chunks[1]);
         self.render_command_log(frame, right_chunks[2]);
         self.render_footer(frame, main_chunks[1]);
@@ -169,13 +594,87 @@ impl App {
 
         if self.show_create_form {
             self.render_create_form(frame);
+            if self.show_overwrite_key_confirm {
+                self.render_overwrite_key_popup(frame);
+            }
+        }
+
+        if self.show_agent_panel {
+            self.render_agent_panel(frame);
+            if self.show_remove_all_agent_confirm {
+                self.render_remove_all_agent_popup(frame);
+            }
+        }
+
+        if self.show_key_details {
+            self.render_key_details_panel(frame);
+        }
+
+        if self.show_agent_add_form {
+            self.render_agent_add_form(frame);
+        }
+
+        if self.show_agent_passphrase_prompt {
+            self.render_agent_passphrase_prompt(frame);
+        }
+
+        if self.show_agent_lock_prompt {
+            self.render_agent_lock_prompt(frame);
+        }
+
+        if self.show_host_form {
+            self.render_host_form(frame);
+        }
+
+        if self.show_connect_prompt {
+            self.render_connect_prompt(frame);
+        }
+
+        if self.show_known_hosts_panel {
+            self.render_known_hosts_panel(frame);
+        }
+
+        if self.show_authorized_keys_panel {
+            self.render_authorized_keys_panel(frame);
+        }
+
+        if self.show_authorized_key_form {
+            self.render_authorized_key_form(frame);
+        }
+
+        if self.show_passphrase_form {
+            self.render_passphrase_form(frame);
         }
     }
 
+    fn render_terminal_too_small(&self, frame: &mut Frame, area: Rect) {
+        let lines = vec![
+            Line::from("Terminal too small".bold()),
+            Line::from(format!("Current size: {}x{}", area.width, area.height)),
+            Line::from(format!(
+                "Required size: {}x{}",
+                MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT
+            )),
+            Line::from(""),
+            Line::from("Resize the terminal or press q to quit"),
+        ];
+        let height = bordered_text_height(&lines, area.width);
+        let notice = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(Color::Yellow)),
+            )
+            .wrap(Wrap { trim: true })
+            .alignment(Alignment::Center);
+        frame.render_widget(notice, centered_rect(area, area.width, height));
+    }
+
     fn create_main_layout(&self, area: Rect) -> Vec<Rect> {
         Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(93), Constraint::Percentage(7)].as_ref())
+            .constraints([Constraint::Min(0), Constraint::Length(3)])
             .split(area)
             .to_vec()
     }
@@ -188,20 +687,28 @@ impl App {
             .to_vec()
     }
 
+    fn create_left_layout(&self, area: Rect) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area)
+            .to_vec()
+    }
+
     fn create_right_layout(&self, area: Rect) -> Vec<Rect> {
         Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Percentage(60),
-                Constraint::Percentage(7),
-                Constraint::Percentage(33),
+                Constraint::Length(3),
+                Constraint::Min(0),
             ])
             .split(area)
             .to_vec()
     }
 
     fn truncate_with_ellipsis(&self, text: &str, max_width: usize) -> String {
-        let visible_end_length = max_width - 10;
+        let visible_end_length = max_width.saturating_sub(10);
         if text.len() <= visible_end_length {
             return text.to_string();
         }
@@ -229,7 +736,12 @@ impl App {
                 } else {
                     Style::default().fg(Color::DarkGray)
                 };
-                ListItem::new(ellipsis_file.to_string()).style(style)
+                let mut line = Line::from(ellipsis_file.to_string());
+                if let Some(security_key) = self.security_key_info(file) {
+                    line.push_span(" ");
+                    line.push_span(security_key.badge().fg(Color::Cyan));
+                }
+                ListItem::new(line).style(style)
             })
             .collect();
 
@@ -242,7 +754,7 @@ impl App {
         let list = List::new(items)
             .block(
                 Block::bordered()
-                    .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
+                    .border_style(self.pane_border_style(Pane::Files))
                     .title(
                         "SSH Files"
                             .fg(Color::Reset)
@@ -265,6 +777,138 @@ impl App {
         );
     }
 
+    fn render_ssh_hosts(&self, frame: &mut Frame, area: Rect) {
+        let available_width = area.width as usize;
+
+        let items: Vec<ListItem> = self
+            .ssh_hosts
+            .iter()
+            .map(|alias| ListItem::new(self.truncate_with_ellipsis(alias, available_width)))
+            .collect();
+
+        let current_selection_info = if self.ssh_hosts.is_empty() {
+            "|0 of 0|".to_string()
+        } else {
+            format!(
+                "|{} of {}|",
+                self.ssh_hosts_state.selected().unwrap_or(0) + 1,
+                self.ssh_hosts.len()
+            )
+        };
+
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .border_style(self.pane_border_style(Pane::Hosts))
+                    .title(
+                        "Hosts"
+                            .fg(Color::Reset)
+                            .bold()
+                            .underlined()
+                            .into_centered_line(),
+                    )
+                    .title_bottom(Line::from(current_selection_info).alignment(Alignment::Center)),
+            )
+            .highlight_style(Style::default().fg(Color::Magenta).slow_blink())
+            .highlight_symbol("➤ ");
+
+        frame.render_stateful_widget(list, area, &mut self.ssh_hosts_state.clone());
+
+        self.render_scrollbar(
+            frame,
+            area,
+            self.ssh_hosts.len(),
+            self.ssh_hosts_state.selected().unwrap_or_default(),
+        );
+    }
+
+    fn pane_border_style(&self, pane: Pane) -> Style {
+        if self.focused_pane == pane {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::Rgb(100, 100, 100))
+        }
+    }
+
+    fn render_host_settings(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = Vec::new();
+        match self.selected_host() {
+            Some(alias) => {
+                let resolved = self.ssh_config.resolve(alias);
+                let user = resolved
+                    .user()
+                    .map(str::to_string)
+                    .or_else(|| std::env::var("USER").ok())
+                    .unwrap_or_default();
+                lines.push(Line::from(vec![
+                    "Host ".bold(),
+                    alias.clone().fg(Color::Magenta).bold(),
+                ]));
+                lines.push(Line::from(format!(
+                    "Connects to {}@{}:{}",
+                    user,
+                    resolved.hostname(),
+                    resolved.port()
+                )));
+
+                let configured = !resolved.identity_files().is_empty();
+                let offered: Vec<PathBuf> = resolved
+                    .expanded_identity_files(&*self.backend)
+                    .into_iter()
+                    .filter(|path| configured || self.backend.exists(path))
+                    .collect();
+                if offered.is_empty() {
+                    lines.push(Line::from("Offers keys: only those loaded in the agent"));
+                } else {
+                    lines.push(Line::from(if configured {
+                        "Offers keys:"
+                    } else {
+                        "Offers keys (ssh defaults):"
+                    }));
+                    for path in offered {
+                        let mut spans = vec![format!("  {}", path.display()).into()];
+                        if !self.backend.exists(&path) {
+                            spans.push(" (missing)".fg(Color::Red));
+                        }
+                        lines.push(Line::from(spans));
+                    }
+                }
+                lines.push(Line::from(""));
+                for (keyword, value) in &resolved.options {
+                    lines.push(Line::from(vec![
+                        format!("{:<24}", keyword).fg(Color::Cyan),
+                        value.clone().into(),
+                    ]));
+                }
+                if !resolved.sources.is_empty() {
+                    lines.push(Line::from(""));
+                    for (source, line) in &resolved.sources {
+                        lines.push(
+                            Line::from(format!("Defined at {}:{}", source.display(), line))
+                                .fg(Color::DarkGray),
+                        );
+                    }
+                }
+            }
+            None => lines.push(Line::from("No hosts found in ~/.ssh/config")),
+        }
+        for warning in &self.ssh_config.warnings {
+            lines.push(Line::from(warning.as_str()).fg(Color::Yellow));
+        }
+
+        frame.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+                Block::default()
+                    .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title("Host Settings".fg(Color::White).bold())
+                    .title_alignment(Alignment::Center),
+            ),
+            area,
+        );
+    }
+
     fn render_scrollbar(
         &self,
         frame: &mut Frame,
@@ -287,22 +931,181 @@ impl App {
     }
 
     fn render_ssh_content(&self, frame: &mut Frame, area: Rect) {
-        let ssh_content = self.load_ssh_content();
+        let file_name = self.selected_file_name().unwrap_or_default();
+        let key_file = self.key_file(file_name);
+        let kind = key_file.map_or(FileKind::Unknown, |file| file.kind);
+        let ssh_content = match key_file {
+            _ if self.ssh_files.is_empty() => "No file selected",
+            Some(KeyFile {
+                content: Some(content),
+                ..
+            }) => content.as_str(),
+            // Private keys are only read when revealed.
+            Some(KeyFile {
+                revealed: Some(content),
+                ..
+            }) => content.as_str(),
+            _ => "Failed to read file content",
+        };
+        let ssh_content: Vec<Line> = match (kind, key_file) {
+            (FileKind::PrivateKey, _) if self.revealed_file.as_deref() != Some(file_name) => {
+                key_file
+                    .and_then(|file| file.private_key.as_ref())
+                    .map(|info| self.private_key_summary(file_name, info))
+                    .unwrap_or_default()
+            }
+            (
+                FileKind::AuthorizedKeys,
+                Some(KeyFile {
+                    authorized_keys: Some(authorized_keys),
+                    ..
+                }),
+            ) => {
+                let mut lines = vec![
+                    Line::from("Press u to enable, disable or edit the options of keys")
+                        .fg(Color::DarkGray),
+                    Line::from(""),
+                ];
+                for entry in &authorized_keys.entries {
+                    let style = if entry.enabled {
+                        Style::default()
+                    } else {
+                        Style::default().fg(Color::DarkGray)
+                    };
+                    lines.push(Line::from(self.format_authorized_key(entry)).style(style));
+                    if !entry.options.is_empty() {
+                        lines.push(
+                            Line::from(format!("    options: {}", entry.options_text()))
+                                .fg(Color::Yellow),
+                        );
+                    }
+                }
+                lines
+            }
+            (
+                FileKind::KnownHosts,
+                Some(KeyFile {
+                    known_hosts: Some(known_hosts),
+                    ..
+                }),
+            ) => {
+                let mut lines = vec![
+                    Line::from("Press k to search, remove or hash entries").fg(Color::DarkGray),
+                    Line::from(""),
+                ];
+                lines.extend(
+                    known_hosts
+                        .entries
+                        .iter()
+                        .map(|entry| Line::from(self.format_known_host_entry(entry))),
+                );
+                lines
+            }
+            _ => ssh_content.lines().map(Line::from).collect(),
+        };
+        let mut block = Block::default()
+            .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
+            .borders(ratatui::widgets::Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(
+                format!("SSH Content ({})", kind.label())
+                    .fg(Color::White)
+                    .bold(),
+            )
+            .title_alignment(Alignment::Center);
+
+        if let Some(key_path) = self.selected_private_key_path() {
+            let hosts = self.hosts_using_key(&key_path);
+            let used_by = if hosts.is_empty() {
+                "|Not used by any host in ~/.ssh/config|".to_string()
+            } else {
+                format!("|Used by hosts: {}|", hosts.join(", "))
+            };
+            block = block.title_bottom(Line::from(used_by).alignment(Alignment::Center));
+        }
+
         frame.render_widget(
-            Paragraph::new(ssh_content).wrap(Wrap { trim: true }).block(
-                Block::default()
-                    .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
-                    .borders(ratatui::widgets::Borders::ALL)
-                    .border_type(BorderType::Rounded)
-                    .title("SSH Content".fg(Color::White).bold())
-                    .title_alignment(Alignment::Center),
-            ),
+            Paragraph::new(ssh_content)
+                .wrap(Wrap { trim: true })
+                .block(block),
             area,
         );
     }
 
+    /// What the content pane shows for a private key instead of its content.
+    fn private_key_summary(&self, file_name: &str, info: &PrivateKeyInfo) -> Vec<Line<'static>> {
+        let label = |text: &str| format!("{:<12}", text).fg(Color::Cyan);
+        let yes_no = |value: Option<bool>| match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+
+        let mut lines = vec![
+            Line::from("The content of this private key is hidden. Press v to reveal it.")
+                .fg(Color::DarkGray),
+            Line::from(""),
+            Line::from(vec![label("Format"), info.format.into()]),
+        ];
+        let encrypted = match info.encrypted {
+            Some(false) => "no".fg(Color::Yellow),
+            encrypted => yes_no(encrypted).into(),
+        };
+        lines.push(Line::from(vec![label("Encrypted"), encrypted]));
+        if let Some(cipher) = &info.cipher {
+            lines.push(Line::from(vec![label("Cipher"), cipher.clone().into()]));
+        }
+        if let Some(kdf) = &info.kdf {
+            lines.push(Line::from(vec![label("KDF"), kdf.clone().into()]));
+        }
+        if let Some(public_key) = &info.public_key {
+            let bits = keys::key_bits(public_key.key_data())
+                .map(|bits| format!(" {}", bits))
+                .unwrap_or_default();
+            lines.push(Line::from(vec![
+                label("Key"),
+                format!(
+                    "{}{} {}",
+                    keys::key_type_label(&public_key.algorithm()),
+                    bits,
+                    keys::fingerprint(public_key, self.fingerprint_hash)
+                )
+                .into(),
+            ]));
+            if !public_key.comment().is_empty() {
+                lines.push(Line::from(vec![
+                    label("Comment"),
+                    public_key.comment().to_string().into(),
+                ]));
+            }
+        }
+
+        let public_key_name = format!("{}.pub", file_name);
+        let public_key_status = match self.key_file(&public_key_name) {
+            Some(file) => match (&file.public_key, &info.public_key) {
+                (Some(public_key), Some(embedded))
+                    if public_key.key_data() == embedded.key_data() =>
+                {
+                    format!("{} (matches)", public_key_name).into()
+                }
+                (Some(_), Some(_)) => {
+                    format!("{} (does not match this key)", public_key_name).fg(Color::Red)
+                }
+                (Some(_), None) => format!("{} (cannot be compared)", public_key_name).into(),
+                (None, _) => format!("{} (unreadable)", public_key_name).fg(Color::Red),
+            },
+            None => "missing".fg(Color::Yellow),
+        };
+        lines.push(Line::from(vec![label("Public key"), public_key_status]));
+        lines
+    }
+
     fn render_ssh_agent_status(&self, frame: &mut Frame, area: Rect) {
-        let agent_status = self.check_ssh_agent_status();
+        let agent_status = if self.agent_locked {
+            Line::from("SSH agent is locked (unlock with X)".fg(Color::Red).bold())
+        } else {
+            Line::from(self.agent_status())
+        };
         frame.render_widget(
             Paragraph::new(agent_status).block(
                 Block::default()
@@ -338,20 +1141,52 @@ impl App {
     fn render_footer(&self, frame: &mut Frame, area: Rect) {
         let footer_text = if self.show_key_bindings {
             "Use ↓↑ to move | Execute: <enter> | Keybindings: ? | Close: <esc>"
+        } else if self.show_overwrite_key_confirm {
+            "Overwrite the existing key: y | Cancel: n"
+        } else if self.show_remove_all_agent_confirm {
+            "Remove all SSH keys from the agent: y | Cancel: n"
+        } else if self.show_passphrase_form {
+            "Next field: <tab> | Change passphrase: <enter> | Close: <esc>"
+        } else if self.show_authorized_key_form {
+            "Next field: <tab> | Toggle restrict: ↓↑ | Save: <enter> | Close: <esc>"
+        } else if self.show_authorized_keys_panel {
+            "Use ↓↑ to move | Enable/disable: <space> | Edit options: e | Close: <esc>"
+        } else if self.show_known_hosts_panel && self.known_hosts_searching {
+            "Type a host name or [host]:port | Done: <enter> | Clear: <esc>"
+        } else if self.show_known_hosts_panel {
+            "Use ↓↑ to move | Search: / | Remove entry: d | Remove host: R | Hash all: H | Close: <esc>"
+        } else if self.show_connect_prompt {
+            "Enter user@host[:port] | Connect: <enter> | Cancel: <esc>"
+        } else if self.show_host_form && self.host_form_state.selected() == Some(6) {
+            "Next field: <tab> | New line: <enter> | Save: ctrl+s | Close: <esc>"
+        } else if self.show_host_form {
+            "Next field: <tab> | Change identity file: ↓↑ | Save: <enter> | Close: <esc>"
+        } else if self.show_agent_lock_prompt {
+            "Next field: <tab> | Submit: <enter> | Cancel: <esc>"
+        } else if self.show_agent_passphrase_prompt {
+            "Enter the passphrase of the SSH key | Add to agent: <enter> | Cancel: <esc>"
+        } else if self.show_agent_add_form {
+            "Next field: <tab> | Change option: ↓↑ | Add to agent: <enter> | Close: <esc>"
+        } else if self.show_key_details {
+            "Switch fingerprint hash (SHA256/MD5): h | Close: <esc>"
+        } else if self.show_agent_panel {
+            "Use ↓↑ to move | Remove from agent: d | Remove all from agent: D | Close: <esc>"
+        } else if self.focused_pane == Pane::Hosts {
+            "Use ↓↑ to move | Switch pane: <tab> | Connect: <enter> | New host: n | Edit host: e | Delete host: d | Keybindings: ? | Quit: q"
         } else {
-            "Use ↓↑ to move | Create: n | Delete: d | Add to agent: a | Remove from agent: r | Copy to clipboard: c | Keybindings: ? | Quit: q"
+            "Use ↓↑ to move | Switch pane: <tab> | Create: n | Delete: d | Add to agent: a | Add with constraints: A | Remove from agent: r | Copy to clipboard: c | Key details: f | Reveal private key: v | Connect: s | Known hosts: k | Authorized keys: u | Authorize key: U | Change passphrase: p | Agent identities: i | Lock agent: x | Unlock agent: X | Keybindings: ? | Quit: q"
         };
-        frame.render_widget(
-            Paragraph::new(footer_text).block(
-                Block::default()
-                    .borders(ratatui::widgets::Borders::ALL)
-                    .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
-                    .border_type(BorderType::Rounded)
-                    .title("Information".fg(Color::White).bold())
-                    .title_alignment(Alignment::Center),
-            ),
-            area,
-        );
+        let mut block = Block::default()
+            .borders(ratatui::widgets::Borders::ALL)
+            .border_style(Style::default().fg(Color::Rgb(100, 100, 100)))
+            .border_type(BorderType::Rounded)
+            .title("Information".fg(Color::White).bold())
+            .title_alignment(Alignment::Center);
+        if let Some(status) = self.jobs.status() {
+            block = block
+                .title_top(Line::from(format!(" {} ", status).fg(Color::Yellow)).right_aligned());
+        }
+        frame.render_widget(Paragraph::new(footer_text).block(block), area);
     }
 
     fn render_key_bindings_popup(&mut self, frame: &mut Frame) {
@@ -368,24 +1203,20 @@ impl App {
                 top: 1,
             });
 
-        let popup_area = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(30), Constraint::Percentage(30)].as_ref())
-            .split(frame.area())[1];
-
-        let popup_rect = Rect::new(
-            popup_area.x + popup_area.width / 3,
-            popup_area.y / 2,
-            popup_area.width / 3,
-            popup_area.y + popup_area.height / 6,
-        );
-
-        let items: Vec<ListItem> = self
+        let items: Vec<String> = self
             .key_bindings
             .items
             .iter()
-            .map(|item| ListItem::from(format!("{} {}", item.keycode, item.text)))
+            .map(|item| format!("{} {}", item.keycode, item.text))
             .collect();
+        // Borders and padding take four columns and three rows.
+        let width = items.iter().map(|item| item.width()).max().unwrap_or(0) + 4;
+        let popup_rect = centered_rect(
+            frame.area(),
+            u16::try_from(width).unwrap_or(u16::MAX),
+            u16::try_from(items.len() + 3).unwrap_or(u16::MAX),
+        );
+        let items: Vec<ListItem> = items.into_iter().map(ListItem::from).collect();
 
         let list = List::new(items).block(title).highlight_style(
             Style::default()
@@ -393,9 +1224,80 @@ impl App {
                 .add_modifier(Modifier::BOLD),
         );
 
+        frame.render_widget(Clear, popup_rect);
         frame.render_stateful_widget(list, popup_rect, &mut self.key_bindings.state);
     }
 
+    fn render_key_details_panel(&self, frame: &mut Frame) {
+        // Six detail lines, a blank line and the 11-line randomart, plus borders.
+        let area = frame.area();
+        let popup_area = centered_rect(area, area.width / 2, 20);
+
+        let file_name = self.selected_file_name().unwrap_or_default();
+        let lines = match self.cached_public_key(file_name) {
+            Some(public_key) => {
+                let label = |text: &str| format!("{:<10}", text).fg(Color::Cyan);
+                let bits = keys::key_bits(public_key.key_data())
+                    .map(|bits| bits.to_string())
+                    .unwrap_or_else(|| "?".to_string());
+                let fingerprint_style = |hash: FingerprintHash| {
+                    if hash == self.fingerprint_hash {
+                        Style::default().fg(Color::Magenta).bold()
+                    } else {
+                        Style::default()
+                    }
+                };
+
+                let mut lines = vec![
+                    Line::from(vec![label("File"), file_name.to_string().into()]),
+                    Line::from(vec![
+                        label("Type"),
+                        keys::key_type_label(&public_key.algorithm()).into(),
+                    ]),
+                    Line::from(vec![label("Bits"), bits.into()]),
+                    Line::from(vec![
+                        label("Comment"),
+                        public_key.comment().to_string().into(),
+                    ]),
+                ];
+                for hash in [FingerprintHash::Sha256, FingerprintHash::Md5] {
+                    lines.push(Line::from(vec![
+                        label(hash.label()),
+                        Span::styled(keys::fingerprint(public_key, hash), fingerprint_style(hash)),
+                    ]));
+                }
+                lines.push(Line::from(""));
+                lines.extend(
+                    keys::randomart(public_key, self.fingerprint_hash)
+                        .lines()
+                        .map(|line| Line::from(line.to_string())),
+                );
+                lines
+            }
+            None => vec![Line::from("It's not a ssh key")],
+        };
+
+        let details = Paragraph::new(lines).block(
+            Block::default()
+                .title("Key Details")
+                .title_bottom(
+                    Line::from(format!(
+                        "|Fingerprint hash: {}|",
+                        self.fingerprint_hash.label()
+                    ))
+                    .alignment(Alignment::Center),
+                )
+                .borders(ratatui::widgets::Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title_alignment(Alignment::Center)
+                .border_style(Style::default().fg(Color::Green))
+                .padding(Padding::horizontal(1)),
+        );
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(details, popup_area);
+    }
+
     fn render_confirm_delete_popup(&self, frame: &mut Frame) {
         let title = Block::default()
             .title("Confirm Delete")
@@ -403,38 +1305,486 @@ impl App {
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::Red));
 
-        let popup = Paragraph::new(vec![
-            Line::from("Are you sure you want to delete this SSH key?"),
-            Line::from("Note: You can recover the key from the trash."),
-        ])
-        .block(title)
-        .alignment(Alignment::Left);
+        let lines = match (self.focused_pane, self.selected_host()) {
+            (Pane::Hosts, Some(alias)) => vec![
+                Line::from(format!("Are you sure you want to delete Host {}?", alias)),
+                Line::from("Note: A backup of the config file is kept."),
+            ],
+            _ => {
+                let mut lines = vec![
+                    Line::from("Are you sure you want to delete this SSH key?"),
+                    Line::from("Note: You can recover the key from the trash."),
+                ];
+                let hosts = self
+                    .selected_private_key_path()
+                    .map(|key_path| self.hosts_using_key(&key_path))
+                    .unwrap_or_default();
+                if !hosts.is_empty() {
+                    lines.push(Line::from(""));
+                    lines.push(
+                        Line::from(format!(
+                            "Warning: {} host{} still reference{} this key: {}",
+                            hosts.len(),
+                            if hosts.len() == 1 { "" } else { "s" },
+                            if hosts.len() == 1 { "s" } else { "" },
+                            hosts.join(", ")
+                        ))
+                        .fg(Color::Yellow),
+                    );
+                }
+                lines
+            }
+        };
+        let width = (frame.area().width / 3).max(52);
+        let height = bordered_text_height(&lines, width);
+        let popup_area = centered_rect(frame.area(), width, height);
+        let popup = Paragraph::new(lines)
+            .block(title)
+            .wrap(Wrap { trim: true })
+            .alignment(Alignment::Left);
 
-        let popup_area = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(30), Constraint::Percentage(30)].as_ref())
-            .split(frame.area())[1];
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(popup, popup_area);
+    }
 
+    fn render_agent_panel(&mut self, frame: &mut Frame) {
+        let area = frame.area();
         let popup_area = Rect::new(
-            popup_area.x + popup_area.width / 3,
-            popup_area.y + popup_area.height / 4,
-            popup_area.width / 3,
-            popup_area.height / 3,
+            area.x + area.width / 8,
+            area.y + area.height / 6,
+            area.width * 3 / 4,
+            area.height * 2 / 3,
         );
 
-        frame.render_widget(Clear, popup_area);
-        frame.render_widget(popup, popup_area);
-    }
+        let items: Vec<ListItem> = if let AgentListing::Failed(err) = &self.agent_listing {
+            vec![ListItem::new(err.clone()).style(Style::default().fg(Color::Red))]
+        } else if self.agent_identities.is_empty() {
+            vec![ListItem::new("The agent has no identities")]
+        } else {
+            self.agent_identities
+                .iter()
+                .map(|item| {
+                    let bits = item
+                        .bits
+                        .map(|bits| bits.to_string())
+                        .unwrap_or_else(|| "?".to_string());
+                    let file = item.file.as_deref().unwrap_or("not in ~/.ssh");
+                    let style = if item.file.is_some() {
+                        Style::default()
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    };
+                    let constraints = self
+                        .agent_key_constraints
+                        .get(&item.identity.key_blob)
+                        .map(|constraints| self.format_agent_key_constraints(constraints))
+                        .unwrap_or_default();
+                    ListItem::new(format!(
+                        "{:<10} {:>5}  {}  {}  [{}]{}",
+                        item.key_type,
+                        bits,
+                        item.fingerprint,
+                        item.identity.comment,
+                        file,
+                        constraints
+                    ))
+                    .style(style)
+                })
+                .collect()
+        };
 
-    fn load_ssh_files(&self) -> Vec<String> {
-        let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
-        if ssh_dir.exists() {
-            let mut private_keys = HashSet::new();
-            let mut public_keys = HashSet::new();
-            let mut other_files = Vec::new();
-            if let Ok(entries) = fs::read_dir(ssh_dir) {
-                for entry in entries.filter_map(|e| e.ok()) {
-                    if let Some(file_name) = entry.path().file_name().and_then(|n| n.to_str()) {
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("SSH Agent Identities")
+                    .title_bottom(
+                        Line::from(format!("|{} loaded|", self.agent_identities.len()))
+                            .alignment(Alignment::Center),
+                    )
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Green)),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("➤ ");
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(list, popup_area, &mut self.agent_identities_state);
+    }
+
+    fn format_agent_key_constraints(&self, constraints: &AgentKeyConstraints) -> String {
+        let mut text = String::new();
+        if let Some(expires_at) = constraints.expires_at {
+            let remaining = expires_at
+                .duration_since(SystemTime::now())
+                .map(|remaining| remaining.as_secs())
+                .unwrap_or(0);
+            text.push_str(&format!("  expires in {}", format_lifetime(remaining)));
+        }
+        if constraints.confirm {
+            text.push_str("  confirm");
+        }
+        text
+    }
+
+    fn render_agent_add_form(&self, frame: &mut Frame) {
+        let input_chunks = self.create_form_layout(frame.area(), AGENT_ADD_FORM_FIELD_COUNT);
+        let focused = self.agent_add_form_state.selected();
+
+        let lifetime_input = self.create_select_field(
+            "Lifetime (use arrow keys to change)",
+            &self.lifetime_options,
+            self.selected_lifetime_index,
+            focused == Some(0),
+        );
+        let confirm_input = self.create_select_field(
+            "Confirm on every use (use arrow keys to change)",
+            &self.confirm_options,
+            self.selected_confirm_index,
+            focused == Some(2),
+        );
+
+        frame.render_widget(Clear, input_chunks[0]);
+        frame.render_widget(Clear, input_chunks[2]);
+
+        frame.render_widget(lifetime_input, input_chunks[0]);
+        self.render_input_field(
+            frame,
+            input_chunks[1],
+            "Custom lifetime (e.g. 90m, 1h30m, 2d)",
+            &self.custom_lifetime,
+            focused == Some(1),
+            None,
+        );
+        frame.render_widget(confirm_input, input_chunks[2]);
+    }
+
+    fn render_agent_passphrase_prompt(&self, frame: &mut Frame) {
+        let input_chunks = self.create_form_layout(frame.area(), 1);
+        let file_name = self
+            .pending_agent_add
+            .as_ref()
+            .and_then(|(path, _)| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let title = format!("Passphrase for {}", file_name);
+
+        self.render_input_field(
+            frame,
+            input_chunks[0],
+            &title,
+            &self.agent_passphrase,
+            true,
+            None,
+        );
+    }
+
+    fn render_agent_lock_prompt(&self, frame: &mut Frame) {
+        let focused = self.agent_lock_form_state.selected();
+        match self.agent_lock_action {
+            AgentLockAction::Lock => {
+                let input_chunks = self.create_form_layout(frame.area(), 2);
+                self.render_input_field(
+                    frame,
+                    input_chunks[0],
+                    "Lock password",
+                    &self.agent_lock_password,
+                    focused == Some(0),
+                    None,
+                );
+                self.render_input_field(
+                    frame,
+                    input_chunks[1],
+                    "Re-enter lock password",
+                    &self.agent_lock_password_confirm,
+                    focused == Some(1),
+                    None,
+                );
+            }
+            AgentLockAction::Unlock => {
+                let input_chunks = self.create_form_layout(frame.area(), 1);
+                self.render_input_field(
+                    frame,
+                    input_chunks[0],
+                    "Unlock password",
+                    &self.agent_lock_password,
+                    true,
+                    None,
+                );
+            }
+        }
+    }
+
+    fn render_connect_prompt(&self, frame: &mut Frame) {
+        let input_chunks = self.create_form_layout(frame.area(), 1);
+        self.render_input_field(
+            frame,
+            input_chunks[0],
+            "Connect to (user@host[:port])",
+            &self.connect_destination,
+            true,
+            None,
+        );
+    }
+
+    fn format_known_host_entry(&self, entry: &KnownHostEntry) -> String {
+        let fingerprint = entry
+            .public_key
+            .as_ref()
+            .map(|key| keys::fingerprint(key, self.fingerprint_hash))
+            .unwrap_or_else(|| "unsupported key".to_string());
+        format!(
+            "{:<16} {:<32} {:<24} {}",
+            entry.marker.map(|marker| marker.as_str()).unwrap_or(""),
+            self.truncate_with_ellipsis(&entry.hosts_label(), 42),
+            entry.key_type,
+            fingerprint
+        )
+    }
+
+    fn render_known_hosts_panel(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let popup_area = Rect::new(
+            area.x + area.width / 10,
+            area.y + area.height / 8,
+            area.width * 4 / 5,
+            area.height * 3 / 4,
+        );
+
+        let entries = self.visible_known_hosts();
+        let items: Vec<ListItem> = if entries.is_empty() {
+            vec![ListItem::new("No matching known hosts")]
+        } else {
+            entries
+                .iter()
+                .map(|entry| {
+                    let style = match entry.marker {
+                        Some(Marker::Revoked) => Style::default().fg(Color::Red),
+                        Some(Marker::CertAuthority) => Style::default().fg(Color::Cyan),
+                        None => Style::default(),
+                    };
+                    ListItem::new(self.format_known_host_entry(entry)).style(style)
+                })
+                .collect()
+        };
+
+        let search = if self.known_hosts_searching {
+            format!("|Search: {}|", self.known_hosts_search.value())
+        } else if self.known_hosts_search.is_empty() {
+            format!("|{} entries|", entries.len())
+        } else {
+            format!(
+                "|Search: {} ({} matches)|",
+                self.known_hosts_search.value(),
+                entries.len()
+            )
+        };
+        if self.known_hosts_searching {
+            // The search text is centred in the bottom border.
+            let inner_width = popup_area.width.saturating_sub(2);
+            let search_width = u16::try_from(search.width()).unwrap_or(u16::MAX);
+            let column = u16::try_from(self.known_hosts_search.cursor_column()).unwrap_or(0);
+            frame.set_cursor_position((
+                (popup_area.x + 1 + inner_width.saturating_sub(search_width) / 2 + 9 + column)
+                    .min(popup_area.right().saturating_sub(2)),
+                popup_area.bottom().saturating_sub(1),
+            ));
+        }
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("Known Hosts")
+                    .title_bottom(Line::from(search).alignment(Alignment::Center))
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Green)),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("➤ ");
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(list, popup_area, &mut self.known_hosts_state);
+    }
+
+    fn format_authorized_key(&self, entry: &AuthorizedKey) -> String {
+        let fingerprint = entry
+            .public_key
+            .as_ref()
+            .map(|key| keys::fingerprint(key, self.fingerprint_hash))
+            .unwrap_or_else(|| "unsupported key".to_string());
+        format!(
+            "{} {:<24} {} {}",
+            if entry.enabled { "[x]" } else { "[ ]" },
+            entry.key_type,
+            fingerprint,
+            entry.comment
+        )
+    }
+
+    fn render_authorized_keys_panel(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let popup_area = Rect::new(
+            area.x + area.width / 10,
+            area.y + area.height / 8,
+            area.width * 4 / 5,
+            area.height * 3 / 4,
+        );
+
+        let entries = self
+            .authorized_keys
+            .as_ref()
+            .map(|file| file.entries.as_slice())
+            .unwrap_or_default();
+        let items: Vec<ListItem> = if entries.is_empty() {
+            vec![ListItem::new("No authorized keys")]
+        } else {
+            entries
+                .iter()
+                .map(|entry| {
+                    let mut lines = vec![Line::from(self.format_authorized_key(entry))];
+                    if !entry.options.is_empty() {
+                        lines.push(
+                            Line::from(format!("      {}", entry.options_text())).fg(Color::Yellow),
+                        );
+                    }
+                    let style = if entry.enabled {
+                        Style::default()
+                    } else {
+                        Style::default().fg(Color::DarkGray)
+                    };
+                    ListItem::new(lines).style(style)
+                })
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("Authorized Keys")
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Green)),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("➤ ");
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(list, popup_area, &mut self.authorized_keys_state);
+    }
+
+    fn render_authorized_key_form(&self, frame: &mut Frame) {
+        let input_chunks = self.create_form_layout(frame.area(), AUTHORIZED_KEY_FORM_FIELD_COUNT);
+        let focused = self.authorized_key_form_state.selected();
+
+        let inputs = [
+            (
+                0,
+                "from (comma-separated host patterns)",
+                &self.authorized_key_from,
+            ),
+            (1, "command (forced command)", &self.authorized_key_command),
+            (
+                2,
+                "expiry-time (YYYYMMDD[HHMM[SS]])",
+                &self.authorized_key_expiry_time,
+            ),
+            (
+                4,
+                "Other options (e.g. no-pty,permitopen=\"host:22\")",
+                &self.authorized_key_other_options,
+            ),
+        ];
+        for (index, title, input) in inputs {
+            self.render_input_field(
+                frame,
+                input_chunks[index],
+                title,
+                input,
+                focused == Some(index),
+                None,
+            );
+        }
+
+        let restrict_input = self.create_select_field(
+            "restrict (use arrow keys to change)",
+            &self.confirm_options,
+            self.selected_authorized_key_restrict_index,
+            focused == Some(3),
+        );
+        frame.render_widget(Clear, input_chunks[3]);
+        frame.render_widget(restrict_input, input_chunks[3]);
+    }
+
+    fn render_host_form(&self, frame: &mut Frame) {
+        // The extra options get four lines, one option per line.
+        let mut heights = [3; HOST_FORM_FIELD_COUNT];
+        heights[6] = 6;
+        let input_chunks = self.create_form_layout_with_heights(frame.area(), &heights);
+        let focused = self.host_form_state.selected();
+        let identity_options: Vec<&str> = self
+            .host_identity_options
+            .iter()
+            .map(String::as_str)
+            .collect();
+
+        let inputs = [
+            (0, "Host alias", &self.host_alias),
+            (1, "HostName", &self.host_hostname),
+            (2, "User", &self.host_user),
+            (3, "Port", &self.host_port),
+            (5, "ProxyJump", &self.host_proxy_jump),
+            (
+                6,
+                "Extra options (one Keyword value per line)",
+                &self.host_extra_options,
+            ),
+        ];
+        for (index, title, input) in inputs {
+            self.render_input_field(
+                frame,
+                input_chunks[index],
+                title,
+                input,
+                focused == Some(index),
+                None,
+            );
+        }
+
+        let identity_input = self.create_select_field(
+            "IdentityFile (use arrow keys to change)",
+            &identity_options,
+            self.selected_host_identity_index,
+            focused == Some(4),
+        );
+        frame.render_widget(Clear, input_chunks[4]);
+        frame.render_widget(identity_input, input_chunks[4]);
+    }
+
+    fn load_ssh_files(&self) -> Vec<String> {
+        let ssh_dir = self.backend.ssh_dir();
+        if self.backend.exists(&ssh_dir) {
+            let mut private_keys = BTreeSet::new();
+            let mut public_keys = BTreeSet::new();
+            let mut other_files = Vec::new();
+            if let Ok(entries) = self.backend.read_dir(&ssh_dir) {
+                for path in entries {
+                    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
                         if file_name.ends_with(".pub") {
                             public_keys.insert(file_name.trim_end_matches(".pub").to_string());
                         } else if !file_name.ends_with(".pub") {
@@ -462,76 +1812,192 @@ impl App {
         }
     }
 
-    fn load_ssh_content(&self) -> String {
-        let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
-        if let Some(selected_file) = self
-            .ssh_files
-            .get(self.ssh_files_state.selected().unwrap_or(0))
+    fn watch_ssh_dir(&mut self) {
+        let ssh_dir = self.backend.ssh_dir();
+        match self
+            .backend
+            .watch(&ssh_dir, self.event_handler.sender.clone())
         {
-            let path = ssh_dir.join(selected_file);
-            read_to_string(path).unwrap_or_else(|_| "Failed to read file content".to_string())
+            Ok(watch) => self.ssh_dir_watch = Some(watch),
+            Err(err) => self.command_log.push(format!(
+                "Not watching {} for changes: {}",
+                ssh_dir.display(),
+                err
+            )),
+        }
+    }
+
+    /// Reloads the files and hosts after a change on disk. The selections stay
+    /// on the same names, or on the other half of a key pair that gained or
+    /// lost its `.pub` file.
+    fn reload_ssh_files(&mut self) {
+        let selected_file = self.selected_file_name().map(str::to_string);
+        let selected_host = self.selected_host().cloned();
+        self.ssh_files = self.load_ssh_files();
+        self.load_ssh_hosts();
+
+        let index = if self.ssh_files.is_empty() {
+            None
         } else {
-            "No file selected".to_string()
+            let index = selected_file.and_then(|name| {
+                let pair = key_pair_name(&name);
+                self.ssh_files
+                    .iter()
+                    .position(|file| *file == name)
+                    .or_else(|| self.ssh_files.iter().position(|file| *file == pair))
+            });
+            Some(index.unwrap_or_else(|| {
+                self.ssh_files_state
+                    .selected()
+                    .unwrap_or(0)
+                    .min(self.ssh_files.len() - 1)
+            }))
+        };
+        self.ssh_files_state.select(index);
+        if let Some(index) =
+            selected_host.and_then(|alias| self.ssh_hosts.iter().position(|host| *host == alias))
+        {
+            self.ssh_hosts_state.select(Some(index));
         }
     }
 
-    fn check_ssh_agent_status(&self) -> String {
-        if let Some(selected_file) = self
+    fn load_ssh_hosts(&mut self) {
+        self.ssh_config = SshConfig::load_user_config(&*self.backend);
+        self.ssh_hosts = self.ssh_config.host_aliases();
+        let selected = if self.ssh_hosts.is_empty() {
+            None
+        } else {
+            Some(
+                self.ssh_hosts_state
+                    .selected()
+                    .unwrap_or(0)
+                    .min(self.ssh_hosts.len() - 1),
+            )
+        };
+        self.ssh_hosts_state.select(selected);
+    }
+
+    /// Path of the private key of the selected SSH key pair, if a pair is selected.
+    fn selected_private_key_path(&self) -> Option<PathBuf> {
+        let selected_file = self
             .ssh_files
+            .get(self.ssh_files_state.selected().unwrap_or(0))?;
+        let key_name = selected_file.strip_suffix(".pub")?;
+        Some(self.backend.ssh_dir().join(key_name))
+    }
+
+    /// Aliases of the hosts whose effective `IdentityFile` list contains `key_path`.
+    fn hosts_using_key(&self, key_path: &Path) -> Vec<String> {
+        self.ssh_hosts
+            .iter()
+            .filter(|alias| {
+                let resolved = self.ssh_config.resolve(alias);
+                !resolved.identity_files().is_empty()
+                    && resolved
+                        .expanded_identity_files(&*self.backend)
+                        .iter()
+                        .any(|path| path == key_path)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Security key details of a pair: the application from the public key,
+    /// the flags from the private key.
+    fn security_key_info(&self, file: &str) -> Option<keys::SecurityKeyInfo> {
+        let key_name = file.strip_suffix(".pub")?;
+        let public = self.key_file(file)?.security_key.as_ref()?;
+        let flags = self
+            .key_file(key_name)
+            .and_then(|private| private.security_key.as_ref())
+            .and_then(|private| private.flags);
+        Some(keys::SecurityKeyInfo {
+            application: public.application.clone(),
+            flags,
+        })
+    }
+
+    fn selected_file_name(&self) -> Option<&str> {
+        self.ssh_files
             .get(self.ssh_files_state.selected().unwrap_or(0))
-        {
-            let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
-            let path = ssh_dir.join(selected_file);
-            if path.exists() {
-                match self.get_fingerprint(&path) {
-                    Ok(fingerprint) => {
-                        if self.is_key_in_agent(&fingerprint) {
-                            "SSH key is added to agent".to_string()
-                        } else {
-                            "SSH key is not added to agent".to_string()
-                        }
-                    }
-                    Err(err) => err,
-                }
-            } else {
-                "It's not a ssh key".to_string()
+            .map(String::as_str)
+    }
+
+    fn selected_host(&self) -> Option<&String> {
+        self.ssh_hosts.get(self.ssh_hosts_state.selected()?)
+    }
+
+    /// Whether the key of the selected file is in the agent, as of the last
+    /// listing.
+    fn agent_status(&self) -> String {
+        let Some(selected_file) = self.selected_file_name() else {
+            return "No file selected".to_string();
+        };
+        let Some(key_blob) = self
+            .cached_public_key(selected_file)
+            .and_then(|public_key| agent::public_key_blob(public_key).ok())
+        else {
+            return "It's not a ssh key".to_string();
+        };
+        match &self.agent_listing {
+            AgentListing::Pending => "Checking SSH agent...".to_string(),
+            AgentListing::Failed(err) => err.clone(),
+            AgentListing::Listed
+                if self
+                    .agent_identities
+                    .iter()
+                    .any(|item| item.identity.key_blob == key_blob) =>
+            {
+                "SSH key is added to agent".to_string()
             }
-        } else {
-            "No file selected".to_string()
+            AgentListing::Listed => "SSH key is not added to agent".to_string(),
         }
     }
 
-    fn get_fingerprint(&self, path: &std::path::Path) -> Result<String, String> {
-        let output = Command::new("ssh-keygen")
-            .arg("-lf")
-            .arg(path)
-            .output()
-            .expect("Failed to execute ssh-keygen");
+    /// Lists the agent identities on a worker thread, superseding a listing
+    /// that is still running.
+    fn refresh_agent_identities(&mut self) {
+        let backend = self.backend.clone();
+        self.agent_listing_job = Some(self.jobs.spawn(AGENT_LIST_JOB, move || {
+            JobOutput::AgentIdentities(
+                backend
+                    .agent()
+                    .and_then(|agent| agent.list_identities())
+                    .map_err(|err| err.to_string()),
+            )
+        }));
+    }
 
-        if output.status.success() {
-            let fingerprint = String::from_utf8_lossy(&output.stdout);
-            Ok(fingerprint
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or("")
-                .to_string())
-        } else {
-            Err("Failed to get SSH key fingerprint".to_string())
-        }
+    /// Re-reads the listed files, and the other half of each key pair, that
+    /// changed since the last frame.
+    fn refresh_inventory(&mut self) {
+        let ssh_dir = self.backend.ssh_dir();
+        let paths = self
+            .ssh_files
+            .iter()
+            .flat_map(|file| [ssh_dir.join(file), ssh_dir.join(key_pair_name(file))]);
+        self.inventory.refresh(&*self.backend, paths);
     }
 
-    fn is_key_in_agent(&self, fingerprint: &str) -> bool {
-        let output = Command::new("ssh-add")
-            .arg("-l")
-            .output()
-            .expect("Failed to execute ssh-add");
+    fn key_file(&self, file_name: &str) -> Option<&KeyFile> {
+        self.inventory.get(&self.backend.ssh_dir().join(file_name))
+    }
 
-        if output.status.success() {
-            let agent_keys = String::from_utf8_lossy(&output.stdout);
-            agent_keys.lines().any(|line| line.contains(fingerprint))
+    /// Public key of a file from the inventory: the `.pub` file of the pair,
+    /// or the one stored in the private key.
+    fn cached_public_key(&self, file_name: &str) -> Option<&PublicKey> {
+        let public_key_name = if file_name.ends_with(".pub") {
+            file_name.to_string()
         } else {
-            false
-        }
+            format!("{}.pub", file_name)
+        };
+        self.key_file(&public_key_name)
+            .and_then(|file| file.public_key.as_ref())
+            .or_else(|| self.key_file(file_name)?.public_key.as_ref())
+    }
+
+    fn read_public_key(&self, path: &Path) -> Result<PublicKey, String> {
+        read_public_key(&*self.backend, path)
     }
 
     fn toggle_keybindings(&mut self) {
@@ -549,6 +2015,61 @@ impl App {
             return;
         }
 
+        if self.show_agent_passphrase_prompt {
+            self.handle_agent_passphrase_key_event(key);
+            return;
+        }
+
+        if self.show_agent_lock_prompt {
+            self.handle_agent_lock_key_event(key);
+            return;
+        }
+
+        if self.show_host_form {
+            self.handle_host_form_key_event(key);
+            return;
+        }
+
+        if self.show_connect_prompt {
+            self.handle_connect_prompt_key_event(key);
+            return;
+        }
+
+        if self.show_known_hosts_panel {
+            self.handle_known_hosts_key_event(key);
+            return;
+        }
+
+        if self.show_authorized_key_form {
+            self.handle_authorized_key_form_key_event(key);
+            return;
+        }
+
+        if self.show_passphrase_form {
+            self.handle_passphrase_form_key_event(key);
+            return;
+        }
+
+        if self.show_authorized_keys_panel {
+            self.handle_authorized_keys_key_event(key);
+            return;
+        }
+
+        if self.show_agent_add_form {
+            self.handle_agent_add_form_key_event(key);
+            return;
+        }
+
+        if self.show_agent_panel {
+            self.handle_agent_panel_key_event(key);
+            return;
+        }
+
+        if self.show_key_details {
+            self.handle_key_details_key_event(key);
+            return;
+        }
+
         if self.show_key_bindings {
             self.handle_key_bindings_key_event(key);
             return;
@@ -557,10 +2078,105 @@ impl App {
         self.handle_general_key_event(key);
     }
 
+    /// Pastes into the focused text input, if any.
+    fn on_paste(&mut self, text: &str) {
+        if let Some(input) = self.focused_input() {
+            input.insert_str(text);
+        }
+        if self.show_known_hosts_panel {
+            self.known_hosts_state.select(Some(0));
+        }
+    }
+
+    fn edit_focused_input(&mut self, key: KeyEvent) {
+        if let Some(input) = self.focused_input() {
+            input.handle_key(key);
+        }
+    }
+
+    /// The text input that receives typed and pasted text, following the
+    /// same precedence as [`App::on_key_event`].
+    fn focused_input(&mut self) -> Option<&mut TextInput> {
+        if self.show_confirm_delete {
+            return None;
+        }
+        if self.show_create_form {
+            if self.show_overwrite_key_confirm {
+                return None;
+            }
+            return match self.create_form_state.selected() {
+                Some(0) => Some(&mut self.key_name),
+                Some(3) => Some(&mut self.passphrase),
+                Some(4) => Some(&mut self.re_passphrase),
+                Some(5) => Some(&mut self.comment),
+                Some(8) => Some(&mut self.sk_application),
+                Some(9) => Some(&mut self.sk_user),
+                _ => None,
+            };
+        }
+        if self.show_agent_passphrase_prompt {
+            return Some(&mut self.agent_passphrase);
+        }
+        if self.show_agent_lock_prompt {
+            return match self.agent_lock_form_state.selected() {
+                Some(1) => Some(&mut self.agent_lock_password_confirm),
+                _ => Some(&mut self.agent_lock_password),
+            };
+        }
+        if self.show_host_form {
+            return match self.host_form_state.selected() {
+                Some(0) => Some(&mut self.host_alias),
+                Some(1) => Some(&mut self.host_hostname),
+                Some(2) => Some(&mut self.host_user),
+                Some(3) => Some(&mut self.host_port),
+                Some(5) => Some(&mut self.host_proxy_jump),
+                Some(6) => Some(&mut self.host_extra_options),
+                _ => None,
+            };
+        }
+        if self.show_connect_prompt {
+            return Some(&mut self.connect_destination);
+        }
+        if self.show_known_hosts_panel {
+            return self
+                .known_hosts_searching
+                .then_some(&mut self.known_hosts_search);
+        }
+        if self.show_authorized_key_form {
+            return match self.authorized_key_form_state.selected() {
+                Some(0) => Some(&mut self.authorized_key_from),
+                Some(1) => Some(&mut self.authorized_key_command),
+                Some(2) => Some(&mut self.authorized_key_expiry_time),
+                Some(4) => Some(&mut self.authorized_key_other_options),
+                _ => None,
+            };
+        }
+        if self.show_passphrase_form {
+            return match self.passphrase_form_state.selected() {
+                Some(1) => Some(&mut self.new_passphrase),
+                Some(2) => Some(&mut self.new_re_passphrase),
+                _ => Some(&mut self.old_passphrase),
+            };
+        }
+        if self.show_authorized_keys_panel {
+            return None;
+        }
+        if self.show_agent_add_form {
+            return match self.agent_add_form_state.selected() {
+                Some(1) => Some(&mut self.custom_lifetime),
+                _ => None,
+            };
+        }
+        None
+    }
+
     fn handle_confirm_delete_key_event(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Enter => {
-                self.confirm_delete_ssh_key();
+                match self.focused_pane {
+                    Pane::Files => self.confirm_delete_ssh_key(),
+                    Pane::Hosts => self.confirm_delete_host(),
+                }
                 self.toggle_confirm_delete();
             }
             KeyCode::Esc => {
@@ -571,69 +2187,187 @@ impl App {
     }
 
     fn handle_create_form_key_event(&mut self, key: KeyEvent) {
-        match key.code {
-            KeyCode::Enter => {
-                if self.passphrase == self.re_passphrase {
-                    self.create_ssh_key();
-                } else {
-                    self.command_log
-                        .push("Passphrases do not match".to_string());
+        if self.show_overwrite_key_confirm {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    self.show_overwrite_key_confirm = false;
+                    self.overwrite_existing_key();
                 }
+                KeyCode::Char('n') | KeyCode::Esc => self.show_overwrite_key_confirm = false,
+                _ => {}
             }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Enter => self.submit_create_form(),
             KeyCode::Esc => self.toggle_create_ssh_key(),
             KeyCode::Tab => self.select_next_form_field(),
             KeyCode::BackTab => self.select_previous_form_field(),
-            KeyCode::Char(c) => self.handle_char_input(c),
-            KeyCode::Backspace => self.handle_backspace(),
-            KeyCode::Delete => self.handle_delete(),
             KeyCode::Up => self.handle_up_key(),
             KeyCode::Down => self.handle_down_key(),
-            _ => {}
+            _ => self.edit_focused_input(key),
         }
     }
 
     fn select_next_form_field(&mut self) {
-        let next_index = (self.create_form_state.selected().unwrap_or(0) + 1) % FORM_FIELD_COUNT;
+        let mut next_index = self.create_form_state.selected().unwrap_or(0);
+        loop {
+            next_index = (next_index + 1) % FORM_FIELD_COUNT;
+            if self.is_create_form_field_visible(next_index) {
+                break;
+            }
+        }
         self.create_form_state.select(Some(next_index));
     }
 
     fn select_previous_form_field(&mut self) {
-        let prev_index = if self.create_form_state.selected().unwrap_or(0) == 0 {
-            FORM_FIELD_COUNT - 1
-        } else {
-            self.create_form_state.selected().unwrap_or(0) - 1
-        };
+        let mut prev_index = self.create_form_state.selected().unwrap_or(0);
+        loop {
+            prev_index = (prev_index + FORM_FIELD_COUNT - 1) % FORM_FIELD_COUNT;
+            if self.is_create_form_field_visible(prev_index) {
+                break;
+            }
+        }
         self.create_form_state.select(Some(prev_index));
     }
 
-    fn handle_char_input(&mut self, c: char) {
-        match self.create_form_state.selected() {
-            Some(0) => self.key_name.push(c),
-            Some(3) => self.passphrase.push(c),
-            Some(4) => self.re_passphrase.push(c),
-            Some(5) => self.comment.push(c),
-            _ => {}
+    /// The bits field is hidden for fixed-size keys, the security key
+    /// options for every other key type.
+    fn is_create_form_field_visible(&self, index: usize) -> bool {
+        match index {
+            2 => self.has_key_bits_field(),
+            6..=9 => keys::is_security_key_type(self.selected_key_type()),
+            _ => true,
         }
     }
 
-    fn handle_backspace(&mut self) {
-        match self.create_form_state.selected() {
-            Some(0) => self.key_name.pop(),
-            Some(3) => self.passphrase.pop(),
-            Some(4) => self.re_passphrase.pop(),
-            Some(5) => self.comment.pop(),
+    fn selected_key_type(&self) -> &'static str {
+        self.key_types[self.selected_key_type_index]
+    }
+
+    fn selected_key_bits(&self) -> Option<&'static str> {
+        self.bits_options.get(self.selected_bits_index).copied()
+    }
+
+    /// Fixed-size key types such as ed25519 have no bits field.
+    fn has_key_bits_field(&self) -> bool {
+        !self.bits_options.is_empty()
+    }
+
+    fn create_form_input(&self, index: usize) -> Option<&TextInput> {
+        match index {
+            0 => Some(&self.key_name),
+            3 => Some(&self.passphrase),
+            4 => Some(&self.re_passphrase),
+            5 => Some(&self.comment),
+            8 => Some(&self.sk_application),
+            9 => Some(&self.sk_user),
             _ => None,
-        };
+        }
     }
 
-    fn handle_delete(&mut self) {
-        match self.create_form_state.selected() {
-            Some(0) => self.key_name.clear(),
-            Some(3) => self.passphrase.clear(),
-            Some(4) => self.re_passphrase.clear(),
-            Some(5) => self.comment.clear(),
-            _ => {}
-        };
+    fn create_form_field_message(&self, index: usize) -> Option<FieldMessage> {
+        self.create_form_error(index)
+            .map(FieldMessage::Error)
+            .or_else(|| self.create_form_hint(index).map(FieldMessage::Hint))
+    }
+
+    fn create_form_error(&self, index: usize) -> Option<String> {
+        match index {
+            0 => validate_key_name(self.key_name.value().trim()).err(),
+            2 => {
+                let bits = self
+                    .selected_key_bits()
+                    .map(|bits| bits.parse().unwrap_or(0));
+                keys::validate_key_bits(self.selected_key_type(), bits).err()
+            }
+            // Only complain about an empty confirmation after a submit attempt.
+            4 if self.passphrase.value() != self.re_passphrase.value()
+                && (self.create_form_submitted || !self.re_passphrase.is_empty()) =>
+            {
+                Some("passphrases do not match".to_string())
+            }
+            5 if self.comment.value().contains(char::is_control) => {
+                Some("must not contain control characters".to_string())
+            }
+            8 => {
+                let application = self.sk_application.value().trim();
+                (!application.is_empty() && !application.starts_with("ssh:"))
+                    .then(|| "must start with ssh:".to_string())
+            }
+            // FIDO2 limits the user handle to 64 bytes.
+            9 => (self.sk_user.value().trim().len() > 64).then(|| "at most 64 bytes".to_string()),
+            _ => None,
+        }
+    }
+
+    fn create_form_hint(&self, index: usize) -> Option<String> {
+        match index {
+            0 => self
+                .create_form_existing_key()
+                .map(|_| "already exists, you will be asked to overwrite it".to_string()),
+            3 => passphrase_strength_hint(self.passphrase.value()).map(str::to_string),
+            _ => None,
+        }
+    }
+
+    /// Path of the key the form would overwrite, if any.
+    fn create_form_existing_key(&self) -> Option<PathBuf> {
+        let key_name = self.key_name.value().trim();
+        if key_name.is_empty() {
+            return None;
+        }
+        let key_path = self.backend.ssh_dir().join(key_name);
+        (self.backend.exists(&key_path) || self.backend.exists(&keys::public_key_path(&key_path)))
+            .then_some(key_path)
+    }
+
+    fn submit_create_form(&mut self) {
+        if self.jobs.is_running(CREATE_KEY_JOB) {
+            // The form closes by itself once the key is written.
+            return;
+        }
+        self.create_form_submitted = true;
+        if let Some(index) = (0..FORM_FIELD_COUNT).find(|&index| {
+            self.is_create_form_field_visible(index) && self.create_form_error(index).is_some()
+        }) {
+            self.create_form_state.select(Some(index));
+            return;
+        }
+        if self.create_form_existing_key().is_some() {
+            self.show_overwrite_key_confirm = true;
+            return;
+        }
+        self.create_ssh_key();
+    }
+
+    fn overwrite_existing_key(&mut self) {
+        let Some(key_path) = self.create_form_existing_key() else {
+            return self.create_ssh_key();
+        };
+        for path in [keys::public_key_path(&key_path), key_path.clone()] {
+            if self.backend.exists(&path) {
+                if let Err(err) = self.backend.trash(&path) {
+                    self.command_log.push(format!(
+                        "Move to trash: {} -> Failed to move to trash: {}",
+                        path.display(),
+                        err
+                    ));
+                    return;
+                }
+            }
+        }
+        self.command_log.push(format!(
+            "Move to trash: {} -> Existing SSH key moved to trash",
+            key_path.display()
+        ));
+        self.create_ssh_key();
+    }
+
+    fn on_key_type_changed(&mut self) {
+        self.bits_options = keys::key_bits_options(self.selected_key_type()).to_vec();
+        self.selected_bits_index = 0;
     }
 
     fn handle_up_key(&mut self) {
@@ -643,6 +2377,11 @@ impl App {
             } else {
                 self.selected_key_type_index - 1
             };
+            self.on_key_type_changed();
+        } else if let Some(6) = self.create_form_state.selected() {
+            self.selected_sk_resident_index = 1 - self.selected_sk_resident_index;
+        } else if let Some(7) = self.create_form_state.selected() {
+            self.selected_sk_verify_required_index = 1 - self.selected_sk_verify_required_index;
         } else if let Some(2) = self.create_form_state.selected() {
             self.selected_bits_index = if self.selected_bits_index == 0 {
                 self.bits_options.len() - 1
@@ -660,6 +2399,11 @@ impl App {
                 } else {
                     self.selected_key_type_index + 1
                 };
+            self.on_key_type_changed();
+        } else if let Some(6) = self.create_form_state.selected() {
+            self.selected_sk_resident_index = 1 - self.selected_sk_resident_index;
+        } else if let Some(7) = self.create_form_state.selected() {
+            self.selected_sk_verify_required_index = 1 - self.selected_sk_verify_required_index;
         } else if let Some(2) = self.create_form_state.selected() {
             self.selected_bits_index = if self.selected_bits_index == self.bits_options.len() - 1 {
                 0
@@ -669,6 +2413,238 @@ impl App {
         }
     }
 
+    fn handle_agent_passphrase_key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.submit_agent_passphrase(),
+            KeyCode::Esc => self.cancel_agent_passphrase_prompt(),
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_agent_lock_key_event(&mut self, key: KeyEvent) {
+        let field_count = match self.agent_lock_action {
+            AgentLockAction::Lock => 2,
+            AgentLockAction::Unlock => 1,
+        };
+        let focused = self.agent_lock_form_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Enter => self.submit_agent_lock_prompt(),
+            KeyCode::Esc => self.close_agent_lock_prompt(),
+            KeyCode::Tab | KeyCode::BackTab => self
+                .agent_lock_form_state
+                .select(Some((focused + 1) % field_count)),
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_known_hosts_key_event(&mut self, key: KeyEvent) {
+        if self.known_hosts_searching {
+            match key.code {
+                KeyCode::Enter => self.known_hosts_searching = false,
+                KeyCode::Esc => {
+                    self.known_hosts_searching = false;
+                    self.known_hosts_search.clear();
+                }
+                _ => self.edit_focused_input(key),
+            }
+            self.known_hosts_state.select(Some(0));
+            return;
+        }
+
+        let count = self.visible_known_hosts().len();
+        match key.code {
+            KeyCode::Up => {
+                let i = self.known_hosts_state.selected().unwrap_or(0);
+                self.known_hosts_state.select(Some(i.saturating_sub(1)));
+            }
+            KeyCode::Down => {
+                let i = self.known_hosts_state.selected().unwrap_or(0);
+                self.known_hosts_state
+                    .select(Some((i + 1).min(count.saturating_sub(1))));
+            }
+            KeyCode::Char('/') => self.known_hosts_searching = true,
+            KeyCode::Char('d') | KeyCode::Delete => self.remove_selected_known_host(),
+            KeyCode::Char('R') => self.remove_known_host(),
+            KeyCode::Char('H') => self.hash_known_hosts(),
+            KeyCode::Esc | KeyCode::Char('k') => self.toggle_known_hosts_panel(),
+            _ => {}
+        }
+    }
+
+    fn handle_authorized_keys_key_event(&mut self, key: KeyEvent) {
+        let count = self
+            .authorized_keys
+            .as_ref()
+            .map_or(0, |file| file.entries.len());
+        match key.code {
+            KeyCode::Up => {
+                let i = self.authorized_keys_state.selected().unwrap_or(0);
+                self.authorized_keys_state.select(Some(i.saturating_sub(1)));
+            }
+            KeyCode::Down => {
+                let i = self.authorized_keys_state.selected().unwrap_or(0);
+                self.authorized_keys_state
+                    .select(Some((i + 1).min(count.saturating_sub(1))));
+            }
+            KeyCode::Char(' ') => self.toggle_selected_authorized_key(),
+            KeyCode::Char('e') | KeyCode::Enter => self.open_authorized_key_form(),
+            KeyCode::Esc | KeyCode::Char('u') => self.toggle_authorized_keys_panel(),
+            _ => {}
+        }
+    }
+
+    fn handle_authorized_key_form_key_event(&mut self, key: KeyEvent) {
+        let focused = self.authorized_key_form_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Enter => self.save_authorized_key_form(),
+            KeyCode::Esc => self.show_authorized_key_form = false,
+            KeyCode::Tab => self
+                .authorized_key_form_state
+                .select(Some((focused + 1) % AUTHORIZED_KEY_FORM_FIELD_COUNT)),
+            KeyCode::BackTab => self.authorized_key_form_state.select(Some(
+                (focused + AUTHORIZED_KEY_FORM_FIELD_COUNT - 1) % AUTHORIZED_KEY_FORM_FIELD_COUNT,
+            )),
+            KeyCode::Up | KeyCode::Down | KeyCode::Char(' ') if focused == 3 => {
+                self.selected_authorized_key_restrict_index =
+                    1 - self.selected_authorized_key_restrict_index
+            }
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_passphrase_form_key_event(&mut self, key: KeyEvent) {
+        let focused = self.passphrase_form_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Enter => self.change_passphrase(),
+            KeyCode::Esc => self.close_passphrase_form(),
+            KeyCode::Tab => self
+                .passphrase_form_state
+                .select(Some((focused + 1) % PASSPHRASE_FORM_FIELD_COUNT)),
+            KeyCode::BackTab => self.passphrase_form_state.select(Some(
+                (focused + PASSPHRASE_FORM_FIELD_COUNT - 1) % PASSPHRASE_FORM_FIELD_COUNT,
+            )),
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_connect_prompt_key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.submit_connect_prompt(),
+            KeyCode::Esc => self.show_connect_prompt = false,
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_host_form_key_event(&mut self, key: KeyEvent) {
+        let focused = self.host_form_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Enter if focused == 6 => self.host_extra_options.insert_newline(),
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.save_host_form()
+            }
+            KeyCode::Enter => self.save_host_form(),
+            KeyCode::Esc => self.show_host_form = false,
+            KeyCode::Tab => self
+                .host_form_state
+                .select(Some((focused + 1) % HOST_FORM_FIELD_COUNT)),
+            KeyCode::BackTab => self.host_form_state.select(Some(
+                (focused + HOST_FORM_FIELD_COUNT - 1) % HOST_FORM_FIELD_COUNT,
+            )),
+            KeyCode::Up if focused == 4 => {
+                self.selected_host_identity_index = if self.selected_host_identity_index == 0 {
+                    self.host_identity_options.len() - 1
+                } else {
+                    self.selected_host_identity_index - 1
+                }
+            }
+            KeyCode::Down if focused == 4 => {
+                self.selected_host_identity_index =
+                    (self.selected_host_identity_index + 1) % self.host_identity_options.len()
+            }
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_agent_add_form_key_event(&mut self, key: KeyEvent) {
+        let focused = self.agent_add_form_state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Enter => self.submit_agent_add_form(),
+            KeyCode::Esc => self.toggle_agent_add_form(),
+            KeyCode::Tab => self
+                .agent_add_form_state
+                .select(Some((focused + 1) % AGENT_ADD_FORM_FIELD_COUNT)),
+            KeyCode::BackTab => self.agent_add_form_state.select(Some(
+                (focused + AGENT_ADD_FORM_FIELD_COUNT - 1) % AGENT_ADD_FORM_FIELD_COUNT,
+            )),
+            KeyCode::Char(' ') if focused == 2 => {
+                self.selected_confirm_index = 1 - self.selected_confirm_index
+            }
+            KeyCode::Up if focused == 0 => {
+                self.selected_lifetime_index = if self.selected_lifetime_index == 0 {
+                    self.lifetime_options.len() - 1
+                } else {
+                    self.selected_lifetime_index - 1
+                }
+            }
+            KeyCode::Down if focused == 0 => {
+                self.selected_lifetime_index =
+                    (self.selected_lifetime_index + 1) % self.lifetime_options.len()
+            }
+            KeyCode::Up | KeyCode::Down if focused == 2 => {
+                self.selected_confirm_index = 1 - self.selected_confirm_index
+            }
+            _ => self.edit_focused_input(key),
+        }
+    }
+
+    fn handle_key_details_key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('h') => self.fingerprint_hash = self.fingerprint_hash.toggle(),
+            KeyCode::Esc | KeyCode::Char('f') => self.show_key_details = false,
+            _ => {}
+        }
+    }
+
+    fn handle_agent_panel_key_event(&mut self, key: KeyEvent) {
+        if self.show_remove_all_agent_confirm {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    self.show_remove_all_agent_confirm = false;
+                    self.remove_all_agent_identities();
+                }
+                KeyCode::Char('n') | KeyCode::Esc => self.show_remove_all_agent_confirm = false,
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Up => self.select_previous_agent_identity(),
+            KeyCode::Down => self.select_next_agent_identity(),
+            KeyCode::Char('d') | KeyCode::Delete => self.remove_selected_agent_identity(),
+            KeyCode::Char('D') => self.confirm_remove_all_agent_identities(),
+            KeyCode::Esc | KeyCode::Char('i') => self.toggle_agent_panel(),
+            _ => {}
+        }
+    }
+
+    fn select_previous_agent_identity(&mut self) {
+        let i = match self.agent_identities_state.selected() {
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+        self.agent_identities_state.select(Some(i));
+    }
+
+    fn select_next_agent_identity(&mut self) {
+        let i = match self.agent_identities_state.selected() {
+            Some(i) if i + 1 < self.agent_identities.len() => i + 1,
+            Some(i) => i,
+            None => 0,
+        };
+        self.agent_identities_state.select(Some(i));
+    }
+
     fn handle_key_bindings_key_event(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Enter => self.execute_selected_key_binding(),
@@ -720,43 +2696,95 @@ impl App {
 
     fn select_next_ssh_file(&mut self) {
         let i = match self.ssh_files_state.selected() {
-            Some(i) => {
-                if i >= self.ssh_files.len() - 1 {
-                    i
-                } else {
-                    i + 1
-                }
-            }
+            Some(i) if i + 1 < self.ssh_files.len() => i + 1,
+            Some(i) => i,
+            None if self.ssh_files.is_empty() => return,
             None => 0,
         };
         self.ssh_files_state.select(Some(i));
+        self.hide_revealed_file();
     }
 
     fn select_previous_ssh_file(&mut self) {
         let i = match self.ssh_files_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    0
-                } else {
-                    i - 1
-                }
-            }
+            Some(i) => i.saturating_sub(1),
+            None if self.ssh_files.is_empty() => return,
             None => 0,
         };
         self.ssh_files_state.select(Some(i));
+        self.hide_revealed_file();
+    }
+
+    fn toggle_focused_pane(&mut self) {
+        self.focused_pane = match self.focused_pane {
+            Pane::Files => Pane::Hosts,
+            Pane::Hosts => Pane::Files,
+        };
+    }
+
+    fn select_next_ssh_host(&mut self) {
+        let i = match self.ssh_hosts_state.selected() {
+            Some(i) if i + 1 < self.ssh_hosts.len() => i + 1,
+            Some(i) => i,
+            None if self.ssh_hosts.is_empty() => return,
+            None => 0,
+        };
+        self.ssh_hosts_state.select(Some(i));
+    }
+
+    fn select_previous_ssh_host(&mut self) {
+        let i = match self.ssh_hosts_state.selected() {
+            Some(i) => i.saturating_sub(1),
+            None if self.ssh_hosts.is_empty() => return,
+            None => 0,
+        };
+        self.ssh_hosts_state.select(Some(i));
     }
 
     fn handle_general_key_event(&mut self, key: KeyEvent) {
+        if self.focused_pane == Pane::Hosts {
+            match key.code {
+                KeyCode::Char('n') => return self.open_host_form(None),
+                KeyCode::Char('e') => return self.open_host_form(self.selected_host().cloned()),
+                KeyCode::Enter => return self.connect_to_selected_host(),
+                KeyCode::Char('d') => {
+                    if self.selected_host().is_some() {
+                        self.toggle_confirm_delete();
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+
         match (key.modifiers, key.code) {
             (_, KeyCode::Char('q')) => self.quit(),
             (_, KeyCode::Char('?')) => self.toggle_keybindings(),
             (_, KeyCode::Char('n')) => self.toggle_create_ssh_key(),
             (_, KeyCode::Char('a')) => self.add_ssh_key_to_agent(),
+            (_, KeyCode::Char('A')) => self.toggle_agent_add_form(),
             (_, KeyCode::Char('d')) => self.toggle_confirm_delete(),
             (_, KeyCode::Char('c')) => self.copy_ssh_key_to_clipboard(),
             (_, KeyCode::Char('r')) => self.remove_ssh_key_from_agent(),
-            (_, KeyCode::Down) => self.select_next_ssh_file(),
-            (_, KeyCode::Up) => self.select_previous_ssh_file(),
+            (_, KeyCode::Char('i')) => self.toggle_agent_panel(),
+            (_, KeyCode::Char('f')) => self.show_key_details = true,
+            (_, KeyCode::Char('v')) => self.toggle_reveal_private_key(),
+            (_, KeyCode::Char('s')) => self.open_connect_prompt(),
+            (_, KeyCode::Char('k')) => self.toggle_known_hosts_panel(),
+            (_, KeyCode::Char('u')) => self.toggle_authorized_keys_panel(),
+            (_, KeyCode::Char('U')) => self.authorize_selected_public_key(),
+            (_, KeyCode::Char('p')) => self.open_passphrase_form(),
+            (_, KeyCode::Char('x')) => self.open_agent_lock_prompt(AgentLockAction::Lock),
+            (_, KeyCode::Char('X')) => self.open_agent_lock_prompt(AgentLockAction::Unlock),
+            (_, KeyCode::Tab) => self.toggle_focused_pane(),
+            (_, KeyCode::Down) => match self.focused_pane {
+                Pane::Files => self.select_next_ssh_file(),
+                Pane::Hosts => self.select_next_ssh_host(),
+            },
+            (_, KeyCode::Up) => match self.focused_pane {
+                Pane::Files => self.select_previous_ssh_file(),
+                Pane::Hosts => self.select_previous_ssh_host(),
+            },
             _ => {}
         }
     }
@@ -769,70 +2797,237 @@ impl App {
         self.show_create_form = !self.show_create_form;
         if self.show_create_form {
             self.create_form_state.select(Some(0));
+        } else {
+            self.passphrase.zeroize();
+            self.re_passphrase.zeroize();
+            self.create_form_submitted = false;
         }
     }
 
     fn render_create_form(&self, frame: &mut Frame) {
-        let input_chunks = self.create_form_layout(frame.area());
-
-        let name_input = self.create_input_field("Name", &self.key_name, 0);
-        let type_input = self.create_select_field(
+        let focused = self.create_form_state.selected();
+        let titles = [
+            "Name",
             "Type (use arrow keys to change)",
-            &self.key_types,
-            self.selected_key_type_index,
-            1,
-        );
-        let bits_input = self.create_select_field(
             "Bits (use arrow keys to change)",
-            &self.bits_options,
-            self.selected_bits_index,
-            2,
-        );
-        let masked_passphrase = "*".repeat(self.passphrase.len());
-        let masked_re_passphrase = "*".repeat(self.re_passphrase.len());
+            "Passphrase",
+            "Re-enter Passphrase",
+            "Comment",
+            "Resident key, stored on the authenticator (use arrow keys to change)",
+            "Require PIN or biometrics, verify-required (use arrow keys to change)",
+            "Application (default ssh:)",
+            "User ID for resident keys",
+        ];
+        let mut visible_fields: Vec<usize> = (0..FORM_FIELD_COUNT)
+            .filter(|&index| self.is_create_form_field_visible(index))
+            .collect();
+        // Short terminals cannot fit every field, so scroll to the focused one.
+        let fitting = usize::from(frame.area().height / 3).max(1);
+        if visible_fields.len() > fitting {
+            let position = visible_fields
+                .iter()
+                .position(|&index| Some(index) == focused)
+                .unwrap_or(0);
+            let start = position
+                .saturating_sub(fitting - 1)
+                .min(visible_fields.len() - fitting);
+            visible_fields = visible_fields[start..start + fitting].to_vec();
+        }
 
-        let passphrase_input = self.create_input_field("Passphrase", &masked_passphrase, 3);
-        let re_passphrase_input =
-            self.create_input_field("Re-enter Passphrase", &masked_re_passphrase, 4);
-        let comment_input = self.create_input_field("Comment", &self.comment, 5);
+        let input_chunks = self.create_form_layout(frame.area(), visible_fields.len());
+        for (index, chunk) in visible_fields.into_iter().zip(input_chunks) {
+            let message = self.create_form_field_message(index);
+            if let Some(input) = self.create_form_input(index) {
+                self.render_input_field(
+                    frame,
+                    chunk,
+                    titles[index],
+                    input,
+                    focused == Some(index),
+                    message,
+                );
+                continue;
+            }
 
-        frame.render_widget(Clear, input_chunks[0]);
-        frame.render_widget(Clear, input_chunks[1]);
-        frame.render_widget(Clear, input_chunks[2]);
-        frame.render_widget(Clear, input_chunks[3]);
-        frame.render_widget(Clear, input_chunks[4]);
-        frame.render_widget(Clear, input_chunks[5]);
+            let value = match index {
+                1 => self.selected_key_type(),
+                2 => self.selected_key_bits().unwrap_or_default(),
+                6 => self.confirm_options[self.selected_sk_resident_index],
+                _ => self.confirm_options[self.selected_sk_verify_required_index],
+            };
+            let field = self.create_field(titles[index], value, focused == Some(index), message);
+            frame.render_widget(Clear, chunk);
+            frame.render_widget(field, chunk);
+        }
+    }
+
+    fn render_overwrite_key_popup(&self, frame: &mut Frame) {
+        let key_path = self.create_form_existing_key().unwrap_or_default();
+        let lines = vec![
+            Line::from(format!(
+                "{} already exists. Overwrite it?",
+                key_path.display()
+            )),
+            Line::from("Note: The existing key pair is moved to the trash."),
+            Line::from(""),
+            Line::from("Overwrite: y | Cancel: n"),
+        ];
+        let width = frame.area().width / 2;
+        let height = bordered_text_height(&lines, width);
+        let popup = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title("Overwrite Key")
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(Color::Red)),
+            )
+            .wrap(Wrap { trim: true })
+            .alignment(Alignment::Left);
+
+        let popup_area = centered_rect(frame.area(), width, height);
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_remove_all_agent_popup(&self, frame: &mut Frame) {
+        let count = self.agent_identities.len();
+        let question = if count == 1 {
+            "Remove the SSH key from the agent?".to_string()
+        } else {
+            format!("Remove all {} SSH keys from the agent?", count)
+        };
+        let lines = vec![
+            Line::from(question),
+            Line::from("Note: The key files are kept, add them again with a."),
+            Line::from(""),
+            Line::from("Remove: y | Cancel: n"),
+        ];
+        let width = frame.area().width / 2;
+        let height = bordered_text_height(&lines, width);
+        let popup = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title("Remove All From Agent")
+                    .borders(ratatui::widgets::Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(Color::Red)),
+            )
+            .wrap(Wrap { trim: true })
+            .alignment(Alignment::Left);
+
+        let popup_area = centered_rect(frame.area(), width, height);
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(popup, popup_area);
+    }
+
+    fn render_passphrase_form(&self, frame: &mut Frame) {
+        let input_chunks = self.create_form_layout(frame.area(), PASSPHRASE_FORM_FIELD_COUNT);
+        let focused = self.passphrase_form_state.selected();
+        let key_name = self
+            .passphrase_form_key
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        self.render_input_field(
+            frame,
+            input_chunks[0],
+            &format!("Old passphrase for {} (empty if none)", key_name),
+            &self.old_passphrase,
+            focused == Some(0),
+            None,
+        );
+        self.render_input_field(
+            frame,
+            input_chunks[1],
+            "New passphrase (empty for none)",
+            &self.new_passphrase,
+            focused == Some(1),
+            None,
+        );
+        self.render_input_field(
+            frame,
+            input_chunks[2],
+            "Re-enter new passphrase",
+            &self.new_re_passphrase,
+            focused == Some(2),
+            None,
+        );
+    }
 
-        frame.render_widget(name_input, input_chunks[0]);
-        frame.render_widget(type_input, input_chunks[1]);
-        frame.render_widget(bits_input, input_chunks[2]);
-        frame.render_widget(passphrase_input, input_chunks[3]);
-        frame.render_widget(re_passphrase_input, input_chunks[4]);
-        frame.render_widget(comment_input, input_chunks[5]);
+    fn create_form_layout(&self, area: Rect, field_count: usize) -> Vec<Rect> {
+        self.create_form_layout_with_heights(area, &vec![3; field_count])
     }
 
-    fn create_form_layout(&self, area: Rect) -> Vec<Rect> {
+    fn create_form_layout_with_heights(&self, area: Rect, heights: &[u16]) -> Vec<Rect> {
         Layout::default()
             .direction(Direction::Vertical)
             .constraints(
-                (0..FORM_FIELD_COUNT)
-                    .map(|_| Constraint::Length(3))
+                heights
+                    .iter()
+                    .map(|&height| Constraint::Length(height))
                     .collect::<Vec<_>>(),
             )
-            .split(Rect::new(
-                area.x + area.width / 4,
-                area.y + area.height / 6,
-                area.width / 2,
-                area.height / 2,
-            ))
+            .split(centered_rect(area, area.width / 2, heights.iter().sum()))
             .to_vec()
     }
 
-    fn create_input_field<'a>(&self, title: &str, value: &'a str, index: usize) -> Paragraph<'a> {
-        let border_style = if self.create_form_state.selected() == Some(index) {
-            Style::default().fg(Color::Green)
-        } else {
-            Style::default()
+    /// Renders a text input. The text scrolls to keep the cursor in view and
+    /// the focused input gets the terminal cursor.
+    fn render_input_field(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        input: &TextInput,
+        focused: bool,
+        message: Option<FieldMessage>,
+    ) {
+        let width = area.width.saturating_sub(2);
+        let height = area.height.saturating_sub(2);
+        let scroll = input.scroll_offset(width);
+        let line_scroll = input.line_scroll_offset(height);
+        let field = self
+            .create_field(title, input.display_text(), focused, message)
+            .scroll((line_scroll, scroll));
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(field, area);
+
+        if focused && width > 0 && area.height > 2 {
+            let column = u16::try_from(input.cursor_column()).unwrap_or(u16::MAX);
+            let line = u16::try_from(input.cursor_line()).unwrap_or(u16::MAX);
+            frame.set_cursor_position((
+                area.x + 1 + column.saturating_sub(scroll),
+                area.y + 1 + line.saturating_sub(line_scroll),
+            ));
+        }
+    }
+
+    /// A form field with an optional message appended to its title. Errors
+    /// turn the border red, hints yellow.
+    fn create_field<'a>(
+        &self,
+        title: &str,
+        value: impl Into<Text<'a>>,
+        focused: bool,
+        message: Option<FieldMessage>,
+    ) -> Paragraph<'a> {
+        let (border_style, title) = match message {
+            Some(FieldMessage::Error(error)) => (
+                Style::default().fg(Color::Red),
+                format!("{}: {}", title, error),
+            ),
+            Some(FieldMessage::Hint(hint)) => (
+                Style::default().fg(Color::Yellow),
+                format!("{}: {}", title, hint),
+            ),
+            None if focused => (Style::default().fg(Color::Green), title.to_string()),
+            None => (Style::default(), title.to_string()),
         };
 
         Paragraph::new(value).block(
@@ -840,7 +3035,7 @@ impl App {
                 .borders(ratatui::widgets::Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(border_style)
-                .title(title.to_string())
+                .title(title)
                 .title_style(border_style),
         )
     }
@@ -850,10 +3045,10 @@ impl App {
         title: &str,
         options: &[&'a str],
         selected_index: usize,
-        index: usize,
+        focused: bool,
     ) -> Paragraph<'a> {
         let selected_option = options[selected_index];
-        let border_style = if self.create_form_state.selected() == Some(index) {
+        let border_style = if focused {
             Style::default().fg(Color::Green)
         } else {
             Style::default()
@@ -870,9 +3065,9 @@ impl App {
     }
 
     fn create_ssh_key(&mut self) {
-        let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
-        let key_type = &self.key_types[self.selected_key_type_index];
-        let key_bits = self.bits_options[self.selected_bits_index];
+        let ssh_dir = self.backend.ssh_dir();
+        let key_type = self.selected_key_type();
+        let key_bits = self.selected_key_bits();
         let now = SystemTime::now();
         let current_time = now
             .duration_since(UNIX_EPOCH)
@@ -880,110 +3075,316 @@ impl App {
             .as_secs()
             .to_string();
 
-        let key_name_with_fallback = if self.key_name.trim().is_empty() {
-            "id_".to_string() + key_type + "_" + &current_time
+        let key_name_with_fallback = if self.key_name.value().trim().is_empty() {
+            "id_".to_string() + &key_type.replace('-', "_") + "_" + &current_time
         } else {
-            self.key_name.trim().to_string()
+            self.key_name.value().trim().to_string()
         };
 
         let key_path = ssh_dir.join(&key_name_with_fallback);
-        let key_path_str = key_path.display().to_string();
-
-        let output = Command::new("ssh-keygen")
-            .arg("-t")
-            .arg(key_type)
-            .arg("-b")
-            .arg(key_bits)
-            .arg("-f")
-            .arg(&key_path)
-            .arg("-N")
-            .arg(&self.passphrase)
-            .arg("-C")
-            .arg(&self.comment)
-            .output()
-            .expect("Failed to execute ssh-keygen");
-
-        let masked_passphrase = "*".repeat(self.passphrase.len());
-        self.command_log.push(format!(
-            "ssh-keygen -t {} -b {} -f {} -N {} -C {}",
-            key_type, key_bits, key_path_str, masked_passphrase, self.comment
-        ));
-        if output.status.success() {
-            self.ssh_files = self.load_ssh_files();
-            self.ssh_files_state.select(Some(0));
-            self.show_create_form = false;
-            self.clear_input_fields();
-            self.command_log
-                .push(format!("SSH key created: {}", key_path.display()));
-        } else {
+
+        if self.backend.exists(&key_path) {
             self.command_log.push(format!(
-                "Failed to create SSH key: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Failed to create SSH key: {} already exists",
+                key_path.display()
             ));
+            return;
+        }
+
+        let temp_path = self.ssh_keygen_temp_path(&ssh_dir, &key_name_with_fallback);
+        let keygen_args = self.ssh_keygen_args(key_type, key_bits, &temp_path);
+        if keys::is_security_key_type(key_type) {
+            self.pending_security_keygen = Some(PendingSecurityKeygen {
+                args: keygen_args,
+                temp_path,
+                key_path,
+            });
+            return;
+        }
+
+        let backend = self.backend.clone();
+        let key_type = key_type.to_string();
+        let key_bits = key_bits.map(str::to_string);
+        let passphrase = Zeroizing::new(self.passphrase.value().to_string());
+        let comment = self.comment.value().to_string();
+        self.jobs.spawn(CREATE_KEY_JOB, move || {
+            let mut log = Vec::new();
+            let bits = key_bits.as_deref().and_then(|bits| bits.parse().ok());
+            let result = match keys::generate_key_pair(
+                &*backend,
+                &key_path,
+                &key_type,
+                bits,
+                &passphrase,
+                &comment,
+            ) {
+                Err(KeygenError::Unsupported(_)) => create_ssh_key_with_ssh_keygen(
+                    &*backend,
+                    &keygen_args,
+                    &temp_path,
+                    &key_path,
+                    &passphrase,
+                    &mut log,
+                ),
+                result => {
+                    log.push(format!(
+                        "Generate key: -t {}{} -f {} -N {} -C {}",
+                        key_type,
+                        bits_arg(key_bits.as_deref()),
+                        key_path.display(),
+                        "*".repeat(passphrase.len()),
+                        comment
+                    ));
+                    result.map_err(|err| err.to_string())
+                }
+            };
+            JobOutput::KeyCreated {
+                key_path,
+                log,
+                result,
+            }
+        });
+    }
+
+    fn finish_create_ssh_key(&mut self, key_path: &Path, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                // An overwritten key may keep the modification time of the old one.
+                self.inventory.invalidate(key_path);
+                self.inventory.invalidate(&keys::public_key_path(key_path));
+                self.ssh_files = self.load_ssh_files();
+                self.ssh_files_state.select(Some(0));
+                self.show_create_form = false;
+                self.clear_input_fields();
+                self.command_log
+                    .push(format!("SSH key created: {}", key_path.display()));
+            }
+            Err(err) => self
+                .command_log
+                .push(format!("Failed to create SSH key: {}", err)),
+        }
+    }
+
+    // ssh-keygen only takes a passphrase in argv, where other local users can
+    // read it. It generates the key without one into a temporary file, which
+    // is encrypted natively before being moved into place.
+    fn ssh_keygen_temp_path(&self, ssh_dir: &Path, key_name: &str) -> PathBuf {
+        let temp_path = ssh_dir.join(format!(".{}.tmp", key_name));
+        let _ = self.backend.remove_file(&temp_path);
+        let _ = self.backend.remove_file(&keys::public_key_path(&temp_path));
+        temp_path
+    }
+
+    fn ssh_keygen_args(
+        &self,
+        key_type: &str,
+        key_bits: Option<&str>,
+        temp_path: &Path,
+    ) -> Vec<String> {
+        let mut args = vec!["-t".to_string(), key_type.to_string()];
+        if let Some(key_bits) = key_bits {
+            args.extend(["-b".to_string(), key_bits.to_string()]);
+        }
+        if keys::is_security_key_type(key_type) {
+            if self.confirm_options[self.selected_sk_resident_index] == "yes" {
+                args.extend(["-O".to_string(), "resident".to_string()]);
+            }
+            if self.confirm_options[self.selected_sk_verify_required_index] == "yes" {
+                args.extend(["-O".to_string(), "verify-required".to_string()]);
+            }
+            if !self.sk_application.value().trim().is_empty() {
+                args.extend([
+                    "-O".to_string(),
+                    format!("application={}", self.sk_application.value().trim()),
+                ]);
+            }
+            if !self.sk_user.value().trim().is_empty() {
+                args.extend([
+                    "-O".to_string(),
+                    format!("user={}", self.sk_user.value().trim()),
+                ]);
+            }
         }
+        args.extend([
+            "-f".to_string(),
+            temp_path.display().to_string(),
+            "-N".to_string(),
+            String::new(),
+            "-C".to_string(),
+            self.comment.value().to_string(),
+        ]);
+        args
     }
 
     fn clear_input_fields(&mut self) {
         self.key_name.clear();
         self.key_type.clear();
         self.key_bits.clear();
-        self.passphrase.clear();
-        self.re_passphrase.clear();
+        self.passphrase.zeroize();
+        self.re_passphrase.zeroize();
         self.comment.clear();
+        self.sk_application.clear();
+        self.sk_user.clear();
+        self.selected_sk_resident_index = 0;
+        self.selected_sk_verify_required_index = 0;
+        self.create_form_submitted = false;
     }
 
     fn add_ssh_key_to_agent(&mut self) {
-        if let Some(selected_file) = self
-            .ssh_files
-            .get(self.ssh_files_state.selected().unwrap_or(0))
-        {
-            if !selected_file.ends_with(".pub") {
-                self.command_log.push(format!(
-                    "Cannot add: {} is not a private key file of an SSH pair",
-                    selected_file
-                ));
-                return;
-            }
+        self.add_ssh_key_to_agent_with(Constraints::default());
+    }
 
-            let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
-            let path = ssh_dir.join(selected_file.trim_end_matches(".pub"));
+    fn toggle_agent_add_form(&mut self) {
+        if !self.show_agent_add_form && self.ensure_agent_unlocked("add") {
+            return;
+        }
+        self.show_agent_add_form = !self.show_agent_add_form;
+        if self.show_agent_add_form {
+            self.agent_add_form_state.select(Some(0));
+        }
+    }
 
-            match self.get_fingerprint(&path) {
-                Ok(fingerprint) => {
-                    if self.is_key_in_agent(&fingerprint) {
-                        self.command_log.push(format!(
-                            "ssh-add {} -> SSH key is already added to agent",
-                            path.display()
-                        ));
-                        return;
-                    }
-                }
-                Err(err) => {
-                    self.command_log.push(err);
+    fn submit_agent_add_form(&mut self) {
+        let lifetime = match self.lifetime_options[self.selected_lifetime_index] {
+            "none" => None,
+            "custom" => match agent::parse_lifetime(self.custom_lifetime.value()) {
+                Some(lifetime) => Some(lifetime),
+                None => {
+                    self.command_log.push(format!(
+                        "Invalid lifetime: {:?} (use e.g. 600, 90m, 1h30m, 2d)",
+                        self.custom_lifetime.value()
+                    ));
                     return;
                 }
-            }
+            },
+            preset => agent::parse_lifetime(preset),
+        };
+        let constraints = Constraints {
+            lifetime,
+            confirm: self.confirm_options[self.selected_confirm_index] == "yes",
+        };
 
-            let output = Command::new("ssh-add")
-                .arg(&path)
-                .output()
-                .expect("Failed to execute ssh-add");
+        self.show_agent_add_form = false;
+        self.custom_lifetime.clear();
+        self.add_ssh_key_to_agent_with(constraints);
+    }
 
-            if output.status.success() {
+    fn add_ssh_key_to_agent_with(&mut self, constraints: Constraints) {
+        if self.ensure_agent_unlocked("add") {
+            return;
+        }
+        if let Some(selected_file) = self
+            .ssh_files
+            .get(self.ssh_files_state.selected().unwrap_or(0))
+        {
+            if !selected_file.ends_with(".pub") {
                 self.command_log.push(format!(
-                    "ssh-add {} -> SSH key added to agent",
-                    path.display()
+                    "Cannot add: {} is not a private key file of an SSH pair",
+                    selected_file
                 ));
-            } else {
+                return;
+            }
+
+            let ssh_dir = self.backend.ssh_dir();
+            let path = ssh_dir.join(selected_file.trim_end_matches(".pub"));
+            self.spawn_add_to_agent(path, constraints, None);
+        }
+    }
+
+    /// Adds the key at `path` to the agent on a worker thread. Without a
+    /// passphrase an encrypted key ends with [`AgentAddOutcome::NeedsPassphrase`].
+    fn spawn_add_to_agent(
+        &mut self,
+        path: PathBuf,
+        constraints: Constraints,
+        passphrase: Option<Zeroizing<String>>,
+    ) {
+        let backend = self.backend.clone();
+        self.jobs.spawn(AGENT_ADD_JOB, move || {
+            let outcome = add_to_agent(
+                &*backend,
+                &path,
+                &constraints,
+                passphrase.as_ref().map(|passphrase| passphrase.as_str()),
+            );
+            JobOutput::AgentAdd {
+                path,
+                constraints,
+                outcome,
+            }
+        });
+    }
+
+    fn finish_add_ssh_key_to_agent(
+        &mut self,
+        path: &Path,
+        constraints: Constraints,
+        outcome: AgentAddOutcome,
+    ) {
+        match outcome {
+            AgentAddOutcome::Added(public_key) => {
+                self.cancel_agent_passphrase_prompt();
+                self.refresh_agent_identities();
+                if !constraints.is_empty() {
+                    if let Ok(key_blob) = agent::public_key_blob(&public_key) {
+                        self.agent_key_constraints.insert(
+                            key_blob,
+                            AgentKeyConstraints {
+                                expires_at: constraints.lifetime.map(|lifetime| {
+                                    SystemTime::now() + Duration::from_secs(lifetime.into())
+                                }),
+                                confirm: constraints.confirm,
+                            },
+                        );
+                    }
+                }
                 self.command_log.push(format!(
-                    "ssh-add {} -> Failed to add SSH key to agent: {}",
+                    "Add to agent: {}{} -> SSH key added to agent",
                     path.display(),
-                    String::from_utf8_lossy(&output.stderr)
+                    describe_constraints(&constraints)
+                ))
+            }
+            AgentAddOutcome::AlreadyAdded => self.command_log.push(format!(
+                "Add to agent: {} -> SSH key is already added to agent",
+                path.display()
+            )),
+            AgentAddOutcome::NeedsPassphrase => {
+                self.pending_agent_add = Some((path.to_path_buf(), constraints));
+                self.agent_passphrase.zeroize();
+                self.show_agent_passphrase_prompt = true;
+            }
+            AgentAddOutcome::IncorrectPassphrase => {
+                self.agent_passphrase.zeroize();
+                self.command_log.push(format!(
+                    "Add to agent: {} -> Incorrect passphrase",
+                    path.display()
                 ));
             }
+            AgentAddOutcome::Failed(message) => {
+                self.cancel_agent_passphrase_prompt();
+                self.command_log.push(message);
+            }
         }
     }
 
+    fn cancel_agent_passphrase_prompt(&mut self) {
+        self.show_agent_passphrase_prompt = false;
+        self.pending_agent_add = None;
+        self.agent_passphrase.zeroize();
+    }
+
+    fn submit_agent_passphrase(&mut self) {
+        let Some((path, constraints)) = self.pending_agent_add.clone() else {
+            self.cancel_agent_passphrase_prompt();
+            return;
+        };
+        if self.jobs.is_running(AGENT_ADD_JOB) {
+            return;
+        }
+        let passphrase = Zeroizing::new(self.agent_passphrase.value().to_string());
+        self.spawn_add_to_agent(path, constraints, Some(passphrase));
+    }
+
     fn toggle_confirm_delete(&mut self) {
         self.show_confirm_delete = !self.show_confirm_delete;
     }
@@ -993,12 +3394,12 @@ impl App {
             .ssh_files
             .get(self.ssh_files_state.selected().unwrap_or(0))
         {
-            let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
+            let ssh_dir = self.backend.ssh_dir();
             let private_key_path = ssh_dir.join(selected_file.trim_end_matches(".pub"));
             let public_key_path = ssh_dir.join(selected_file);
 
-            let private_key_deleted = delete(&private_key_path).is_ok();
-            let public_key_deleted = delete(&public_key_path).is_ok();
+            let private_key_deleted = self.backend.trash(&private_key_path).is_ok();
+            let public_key_deleted = self.backend.trash(&public_key_path).is_ok();
 
             if private_key_deleted || public_key_deleted {
                 self.command_log.push(format!(
@@ -1015,7 +3416,7 @@ impl App {
                 ));
             } else {
                 let other_file_path = ssh_dir.join(selected_file);
-                if delete(&other_file_path).is_ok() {
+                if self.backend.trash(&other_file_path).is_ok() {
                     self.command_log.push(format!(
                         "Move to trash: {} -> SSH key moved to trash",
                         other_file_path.display()
@@ -1033,6 +3434,33 @@ impl App {
         }
     }
 
+    /// Shows or hides the raw content of the selected private key.
+    fn toggle_reveal_private_key(&mut self) {
+        let Some(file_name) = self.selected_file_name().map(str::to_string) else {
+            return;
+        };
+        if self.revealed_file.is_some() {
+            self.hide_revealed_file();
+            return;
+        }
+        let path = self.backend.ssh_dir().join(&file_name);
+        self.refresh_inventory();
+        if self.inventory.reveal(&*self.backend, &path) {
+            self.command_log.push(format!(
+                "Reveal private key: {} -> Content shown on screen",
+                path.display()
+            ));
+            self.revealed_file = Some(file_name);
+        }
+    }
+
+    /// Hides a revealed private key and wipes its content from memory.
+    fn hide_revealed_file(&mut self) {
+        if let Some(file_name) = self.revealed_file.take() {
+            self.inventory.hide(&self.backend.ssh_dir().join(file_name));
+        }
+    }
+
     fn copy_ssh_key_to_clipboard(&mut self) {
         if let Some(selected_file) = self
             .ssh_files
@@ -1046,17 +3474,18 @@ impl App {
                 return;
             }
 
-            let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
+            let ssh_dir = self.backend.ssh_dir();
             let path = ssh_dir.join(selected_file);
-            match read_to_string(&path) {
-                Ok(content) => {
-                    let mut clipboard = Clipboard::new().unwrap();
-                    clipboard.set_text(content).unwrap();
-                    self.command_log.push(format!(
+            match self.backend.read_to_string(&path) {
+                Ok(content) => match self.backend.set_clipboard(&content) {
+                    Ok(()) => self.command_log.push(format!(
                         "Copy to clipboard: {} -> SSH public key copied to clipboard",
                         path.display()
-                    ));
-                }
+                    )),
+                    Err(err) => self
+                        .command_log
+                        .push(format!("Failed to copy SSH public key: {}", err)),
+                },
                 Err(err) => {
                     self.command_log
                         .push(format!("Failed to copy SSH public key: {}", err));
@@ -1066,6 +3495,9 @@ impl App {
     }
 
     fn remove_ssh_key_from_agent(&mut self) {
+        if self.ensure_agent_unlocked("remove") {
+            return;
+        }
         if let Some(selected_file) = self
             .ssh_files
             .get(self.ssh_files_state.selected().unwrap_or(0))
@@ -1078,43 +3510,1163 @@ impl App {
                 return;
             }
 
-            let ssh_dir = dirs::home_dir().unwrap().join(".ssh");
+            let ssh_dir = self.backend.ssh_dir();
             let path = ssh_dir.join(selected_file.trim_end_matches(".pub"));
 
-            match self.get_fingerprint(&path) {
-                Ok(fingerprint) => {
-                    if !self.is_key_in_agent(&fingerprint) {
+            let public_key = match self.read_public_key(&path) {
+                Ok(public_key) => public_key,
+                Err(err) => {
+                    self.command_log.push(err);
+                    return;
+                }
+            };
+
+            let backend = self.backend.clone();
+            let label = path.display().to_string();
+            self.jobs.spawn(AGENT_REMOVE_JOB, move || {
+                let result = backend.agent().and_then(|agent| {
+                    if !agent.contains(&public_key)? {
+                        return Ok(false);
+                    }
+                    agent.remove_identity(&agent::public_key_blob(&public_key)?)?;
+                    Ok(true)
+                });
+                JobOutput::AgentRemove {
+                    label,
+                    result: result.map_err(|err| err.to_string()),
+                }
+            });
+        }
+    }
+
+    fn finish_remove_from_agent(&mut self, label: &str, result: Result<bool, String>) {
+        match result {
+            Ok(true) => {
+                self.refresh_agent_identities();
+                self.command_log.push(format!(
+                    "Remove from agent: {} -> SSH key removed from agent",
+                    label
+                ))
+            }
+            Ok(false) => self.command_log.push(format!(
+                "Remove from agent: {} -> SSH key is not added to agent",
+                label
+            )),
+            Err(err) => self.command_log.push(format!(
+                "Remove from agent: {} -> Failed to remove SSH key from agent: {}",
+                label, err
+            )),
+        }
+    }
+
+    fn toggle_agent_panel(&mut self) {
+        self.show_agent_panel = !self.show_agent_panel;
+        if self.show_agent_panel {
+            self.refresh_agent_identities();
+        }
+    }
+
+    fn set_agent_identities(&mut self, identities: Result<Vec<Identity>, String>) {
+        let identities = match identities {
+            Ok(identities) => {
+                self.agent_listing = AgentListing::Listed;
+                identities
+            }
+            Err(err) => {
+                self.agent_listing = AgentListing::Failed(err);
+                Vec::new()
+            }
+        };
+
+        self.refresh_inventory();
+        let public_key_files = self.inventory.public_key_files();
+
+        self.agent_key_constraints.retain(|key_blob, _| {
+            identities
+                .iter()
+                .any(|identity| &identity.key_blob == key_blob)
+        });
+
+        self.agent_identities = identities
+            .into_iter()
+            .map(|identity| {
+                let public_key = identity.public_key();
+                AgentIdentityItem {
+                    key_type: public_key
+                        .as_ref()
+                        .map(|key| keys::key_type_label(&key.algorithm()))
+                        .unwrap_or("UNKNOWN"),
+                    bits: public_key
+                        .as_ref()
+                        .and_then(|key| keys::key_bits(key.key_data())),
+                    fingerprint: public_key
+                        .as_ref()
+                        .map(|key| keys::fingerprint(key, self.fingerprint_hash))
+                        .unwrap_or_default(),
+                    file: public_key_files.get(&identity.key_blob).cloned(),
+                    identity,
+                }
+            })
+            .collect();
+
+        let selected = self
+            .agent_identities_state
+            .selected()
+            .unwrap_or(0)
+            .min(self.agent_identities.len().saturating_sub(1));
+        self.agent_identities_state.select(Some(selected));
+    }
+
+    fn remove_selected_agent_identity(&mut self) {
+        if self.ensure_agent_unlocked("remove") {
+            return;
+        }
+        let Some(item) = self
+            .agent_identities
+            .get(self.agent_identities_state.selected().unwrap_or(0))
+        else {
+            return;
+        };
+
+        let label = if item.identity.comment.is_empty() {
+            item.fingerprint.clone()
+        } else {
+            item.identity.comment.clone()
+        };
+        let key_blob = item.identity.key_blob.clone();
+        let backend = self.backend.clone();
+        self.jobs
+            .spawn(AGENT_REMOVE_JOB, move || JobOutput::AgentRemove {
+                label,
+                result: backend
+                    .agent()
+                    .and_then(|agent| agent.remove_identity(&key_blob))
+                    .map(|()| true)
+                    .map_err(|err| err.to_string()),
+            });
+    }
+
+    /// Asks before flushing the agent, which cannot be undone.
+    fn confirm_remove_all_agent_identities(&mut self) {
+        if self.ensure_agent_unlocked("remove") {
+            return;
+        }
+        if self.agent_identities.is_empty() {
+            self.command_log
+                .push("Remove all from agent -> No SSH keys in agent".to_string());
+            return;
+        }
+        self.show_remove_all_agent_confirm = true;
+    }
+
+    fn remove_all_agent_identities(&mut self) {
+        let backend = self.backend.clone();
+        self.jobs.spawn(AGENT_REMOVE_ALL_JOB, move || {
+            JobOutput::AgentRemoveAll(
+                backend
+                    .agent()
+                    .and_then(|agent| agent.remove_all_identities())
+                    .map_err(|err| err.to_string()),
+            )
+        });
+    }
+
+    fn finish_remove_all_agent_identities(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => self
+                .command_log
+                .push("Remove all from agent -> All SSH keys removed from agent".to_string()),
+            Err(err) => self.command_log.push(format!(
+                "Remove all from agent -> Failed to remove SSH keys from agent: {}",
+                err
+            )),
+        }
+        self.refresh_agent_identities();
+    }
+
+    /// Logs why `action` is unavailable and returns `true` if the agent is locked.
+    fn ensure_agent_unlocked(&mut self, action: &str) -> bool {
+        if self.agent_locked {
+            self.command_log.push(format!(
+                "Cannot {}: the SSH agent is locked, unlock it first with X",
+                action
+            ));
+        }
+        self.agent_locked
+    }
+
+    /// The agent may have been locked or unlocked with `ssh-add` since, so
+    /// both actions are always offered and its reply decides.
+    fn open_agent_lock_prompt(&mut self, action: AgentLockAction) {
+        self.agent_lock_action = action;
+        self.agent_lock_form_state.select(Some(0));
+        self.show_agent_lock_prompt = true;
+    }
+
+    fn close_agent_lock_prompt(&mut self) {
+        self.show_agent_lock_prompt = false;
+        self.agent_lock_password.zeroize();
+        self.agent_lock_password_confirm.zeroize();
+    }
+
+    fn submit_agent_lock_prompt(&mut self) {
+        let action = self.agent_lock_action;
+        if action == AgentLockAction::Lock
+            && self.agent_lock_password.value() != self.agent_lock_password_confirm.value()
+        {
+            self.command_log
+                .push("Lock agent -> Passwords do not match".to_string());
+            return;
+        }
+        let password = Zeroizing::new(self.agent_lock_password.value().to_string());
+        self.close_agent_lock_prompt();
+
+        let name = match action {
+            AgentLockAction::Lock => AGENT_LOCK_JOB,
+            AgentLockAction::Unlock => AGENT_UNLOCK_JOB,
+        };
+        let backend = self.backend.clone();
+        self.jobs.spawn(name, move || {
+            JobOutput::AgentLock(lock_agent(&*backend, action, &password))
+        });
+    }
+
+    fn finish_agent_lock(&mut self, outcome: AgentLockOutcome) {
+        let message = match outcome {
+            AgentLockOutcome::Locked => {
+                self.agent_locked = true;
+                "Lock agent -> SSH agent locked".to_string()
+            }
+            AgentLockOutcome::AlreadyLocked => {
+                self.agent_locked = true;
+                "Lock agent -> SSH agent is already locked".to_string()
+            }
+            AgentLockOutcome::Unlocked => {
+                self.agent_locked = false;
+                self.refresh_agent_identities();
+                "Unlock agent -> SSH agent unlocked".to_string()
+            }
+            AgentLockOutcome::NotLocked => {
+                self.agent_locked = false;
+                "Unlock agent -> SSH agent is not locked".to_string()
+            }
+            AgentLockOutcome::IncorrectPassword => "Unlock agent -> Incorrect password".to_string(),
+            AgentLockOutcome::Failed(message) => message,
+        };
+        self.command_log.push(message);
+    }
+
+    fn open_host_form(&mut self, alias: Option<String>) {
+        let entry = match &alias {
+            Some(alias) => {
+                let path = self.host_config_path(Some(alias));
+                let entry = ConfigDocument::load(&*self.backend, &path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|document| document.host_entry(alias));
+                match entry {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => return,
+                    Err(err) => {
                         self.command_log.push(format!(
-                            "ssh-add -d {} -> SSH key is not added to agent",
-                            path.display()
+                            "Edit host: {} -> Cannot edit Host {}: {}",
+                            path.display(),
+                            alias,
+                            err
                         ));
                         return;
                     }
                 }
-                Err(err) => {
-                    self.command_log.push(err);
-                    return;
-                }
             }
+            None => HostEntry::default(),
+        };
 
-            let output = Command::new("ssh-add")
-                .arg("-d")
-                .arg(&path)
-                .output()
-                .expect("Failed to execute ssh-add");
+        self.host_identity_options = vec![HOST_IDENTITY_NONE.to_string()];
+        self.host_identity_options.extend(
+            self.ssh_files
+                .iter()
+                .filter_map(|file| file.strip_suffix(".pub"))
+                .map(|key| format!("~/.ssh/{}", key)),
+        );
+        if !entry.identity_file.is_empty()
+            && !self.host_identity_options.contains(&entry.identity_file)
+        {
+            self.host_identity_options.push(entry.identity_file.clone());
+        }
+        self.selected_host_identity_index = self
+            .host_identity_options
+            .iter()
+            .position(|option| *option == entry.identity_file)
+            .unwrap_or(0);
 
-            if output.status.success() {
+        self.host_alias.set(entry.alias.clone());
+        self.host_hostname.set(entry.hostname.clone());
+        self.host_user.set(entry.user.clone());
+        self.host_port.set(entry.port.clone());
+        self.host_proxy_jump.set(entry.proxy_jump.clone());
+        self.host_extra_options.set(entry.extra_options_text());
+        self.host_form_original_alias = alias;
+        self.host_form_state.select(Some(0));
+        self.show_host_form = true;
+    }
+
+    fn find_host_block(&self, alias: &str) -> Option<&ssh_config::Block> {
+        self.ssh_config.blocks.iter().find(|block| {
+            matches!(&block.kind, BlockKind::Host(patterns) if patterns.iter().any(|p| p == alias))
+        })
+    }
+
+    /// File a host is defined in, defaulting to `~/.ssh/config` for new hosts.
+    fn host_config_path(&self, alias: Option<&str>) -> PathBuf {
+        alias
+            .and_then(|alias| self.find_host_block(alias))
+            .map(|block| block.source.clone())
+            .unwrap_or_else(|| self.backend.ssh_dir().join("config"))
+    }
+
+    fn save_host_form(&mut self) {
+        let alias = self.host_alias.value().trim().to_string();
+        if alias.is_empty() || alias.contains(|c: char| c.is_whitespace() || "*?!#".contains(c)) {
+            self.command_log.push(format!(
+                "Invalid host alias {:?}: it must be a single word without wildcards",
+                alias
+            ));
+            return;
+        }
+        let port = self.host_port.value().trim();
+        if !port.is_empty() && port.parse::<u16>().map_or(true, |port| port == 0) {
+            self.command_log
+                .push(format!("Invalid port {:?}: it must be 1-65535", port));
+            return;
+        }
+        for (name, input) in [
+            ("HostName", &self.host_hostname),
+            ("User", &self.host_user),
+            ("ProxyJump", &self.host_proxy_jump),
+        ] {
+            let value = input.value().trim();
+            if value.contains(char::is_whitespace) {
                 self.command_log.push(format!(
-                    "ssh-add -d {} -> SSH key removed from agent",
-                    path.display()
+                    "Invalid {} {:?}: it must not contain spaces",
+                    name, value
                 ));
+                return;
+            }
+        }
+        let original_alias = self.host_form_original_alias.clone();
+        if original_alias.as_deref() != Some(alias.as_str()) && self.ssh_hosts.contains(&alias) {
+            self.command_log
+                .push(format!("Host {} already exists", alias));
+            return;
+        }
+        let extra_options = match HostEntry::parse_extra_options(self.host_extra_options.value()) {
+            Ok(extra_options) => extra_options,
+            Err(err) => {
+                self.command_log
+                    .push(format!("Invalid extra options: {}", err));
+                return;
+            }
+        };
+
+        let identity_file = &self.host_identity_options[self.selected_host_identity_index];
+        let entry = HostEntry {
+            alias: alias.clone(),
+            hostname: self.host_hostname.value().trim().to_string(),
+            user: self.host_user.value().trim().to_string(),
+            port: port.to_string(),
+            identity_file: if identity_file == HOST_IDENTITY_NONE {
+                String::new()
             } else {
+                identity_file.clone()
+            },
+            proxy_jump: self.host_proxy_jump.value().trim().to_string(),
+            extra_options,
+        };
+
+        let path = self.host_config_path(original_alias.as_deref());
+        let result = ConfigDocument::load(&*self.backend, &path)
+            .map_err(|err| err.to_string())
+            .and_then(|mut document| {
+                document.upsert_host(original_alias.as_deref(), &entry)?;
+                document.save(&*self.backend).map_err(|err| err.to_string())
+            });
+
+        match result {
+            Ok(backup) => {
+                self.show_host_form = false;
+                self.log_config_saved(&path, backup);
+                self.command_log.push(format!(
+                    "Save host: {} -> Host {} saved",
+                    path.display(),
+                    alias
+                ));
+                self.load_ssh_hosts();
+                if let Some(index) = self.ssh_hosts.iter().position(|host| *host == alias) {
+                    self.ssh_hosts_state.select(Some(index));
+                }
+            }
+            Err(err) => self.command_log.push(format!(
+                "Save host: {} -> Failed to save Host {}: {}",
+                path.display(),
+                alias,
+                err
+            )),
+        }
+    }
+
+    fn confirm_delete_host(&mut self) {
+        let Some(alias) = self.selected_host().cloned() else {
+            return;
+        };
+        let path = self.host_config_path(Some(&alias));
+        let result = ConfigDocument::load(&*self.backend, &path)
+            .map_err(|err| err.to_string())
+            .and_then(|mut document| {
+                document.remove_host(&alias)?;
+                document.save(&*self.backend).map_err(|err| err.to_string())
+            });
+
+        match result {
+            Ok(backup) => {
+                self.log_config_saved(&path, backup);
                 self.command_log.push(format!(
-                    "ssh-add -d {} -> Failed to remove SSH key from agent: {}",
+                    "Delete host: {} -> Host {} deleted",
                     path.display(),
-                    String::from_utf8_lossy(&output.stderr)
+                    alias
                 ));
+                self.load_ssh_hosts();
             }
+            Err(err) => self.command_log.push(format!(
+                "Delete host: {} -> Failed to delete Host {}: {}",
+                path.display(),
+                alias,
+                err
+            )),
         }
     }
+
+    fn log_config_saved(&mut self, path: &Path, backup: Option<PathBuf>) {
+        if let Some(backup) = backup {
+            self.command_log.push(format!(
+                "Backup: {} -> {}",
+                path.display(),
+                backup.display()
+            ));
+        }
+    }
+
+    fn connect_to_selected_host(&mut self) {
+        if let Some(alias) = self.selected_host() {
+            self.pending_ssh_session = Some(vec![alias.clone()]);
+        }
+    }
+
+    fn open_connect_prompt(&mut self) {
+        self.connect_destination.clear();
+        self.show_connect_prompt = true;
+    }
+
+    fn submit_connect_prompt(&mut self) {
+        let destination = self.connect_destination.value().trim();
+        if destination.is_empty() || destination.starts_with('-') {
+            self.command_log
+                .push(format!("Invalid destination {:?}", destination));
+            return;
+        }
+
+        // `user@host:port` is not understood by ssh itself, pass the port with -p.
+        let args = match destination.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => {
+                vec!["-p".to_string(), port.to_string(), host.to_string()]
+            }
+            _ => vec![destination.to_string()],
+        };
+        self.show_connect_prompt = false;
+        self.pending_ssh_session = Some(args);
+    }
+
+    fn toggle_known_hosts_panel(&mut self) {
+        self.show_known_hosts_panel = !self.show_known_hosts_panel;
+        if self.show_known_hosts_panel {
+            self.known_hosts_search.clear();
+            self.known_hosts_searching = false;
+            self.known_hosts_state.select(Some(0));
+            match KnownHostsFile::load_user_file(&*self.backend) {
+                Ok(known_hosts) => self.known_hosts = Some(known_hosts),
+                Err(err) => {
+                    self.command_log
+                        .push(format!("Failed to read known_hosts: {}", err));
+                    self.show_known_hosts_panel = false;
+                }
+            }
+        }
+    }
+
+    /// Entries matching the search, by exact host (which also finds hashed
+    /// entries) or by a substring of a plain host name.
+    fn visible_known_hosts(&self) -> Vec<&KnownHostEntry> {
+        let Some(known_hosts) = &self.known_hosts else {
+            return Vec::new();
+        };
+        let search = self.known_hosts_search.value().trim();
+        known_hosts
+            .entries
+            .iter()
+            .filter(|entry| {
+                search.is_empty()
+                    || entry.matches_host(search)
+                    || matches!(&entry.hosts, HostPatterns::Plain(hosts)
+                        if hosts.iter().any(|host| host.contains(search)))
+            })
+            .collect()
+    }
+
+    fn save_known_hosts(&mut self, action: &str, message: String) {
+        let Some(known_hosts) = &self.known_hosts else {
+            return;
+        };
+        let path = known_hosts.path().display().to_string();
+        match known_hosts.save(&*self.backend) {
+            Ok(_) => self
+                .command_log
+                .push(format!("{}: {} -> {}", action, path, message)),
+            Err(err) => self.command_log.push(format!(
+                "{}: {} -> Failed to write known_hosts: {}",
+                action, path, err
+            )),
+        }
+        let count = self.visible_known_hosts().len();
+        let selected = self.known_hosts_state.selected().unwrap_or(0);
+        self.known_hosts_state
+            .select(Some(selected.min(count.saturating_sub(1))));
+    }
+
+    fn remove_selected_known_host(&mut self) {
+        let Some(entry) = self
+            .visible_known_hosts()
+            .get(self.known_hosts_state.selected().unwrap_or(0))
+            .map(|entry| (*entry).clone())
+        else {
+            return;
+        };
+        if let Some(known_hosts) = &mut self.known_hosts {
+            known_hosts.remove_entry(entry.line);
+        }
+        self.save_known_hosts(
+            "Remove known host",
+            format!(
+                "Removed {} {} (line {})",
+                entry.hosts_label(),
+                entry.key_type,
+                entry.line + 1
+            ),
+        );
+    }
+
+    fn remove_known_host(&mut self) {
+        let host = self.known_hosts_search.value().trim().to_string();
+        if host.is_empty() {
+            self.command_log
+                .push("Remove known host: search for a host first with /".to_string());
+            return;
+        }
+        let removed = self
+            .known_hosts
+            .as_mut()
+            .map(|known_hosts| known_hosts.remove_host(&host))
+            .unwrap_or(0);
+        if removed == 0 {
+            self.command_log
+                .push(format!("ssh-keygen -R {} -> Host not found", host));
+            return;
+        }
+        self.save_known_hosts(
+            &format!("ssh-keygen -R {}", host),
+            format!("Removed {} entries", removed),
+        );
+    }
+
+    fn hash_known_hosts(&mut self) {
+        let hashed = self
+            .known_hosts
+            .as_mut()
+            .map(|known_hosts| known_hosts.hash_all())
+            .unwrap_or(0);
+        if hashed == 0 {
+            self.command_log
+                .push("ssh-keygen -H -> No plain host names to hash".to_string());
+            return;
+        }
+        self.save_known_hosts("ssh-keygen -H", format!("Hashed {} host names", hashed));
+    }
+
+    fn toggle_authorized_keys_panel(&mut self) {
+        self.show_authorized_keys_panel = !self.show_authorized_keys_panel;
+        if self.show_authorized_keys_panel {
+            self.authorized_keys_state.select(Some(0));
+            match AuthorizedKeysFile::load_user_file(&*self.backend) {
+                Ok(authorized_keys) => self.authorized_keys = Some(authorized_keys),
+                Err(err) => {
+                    self.command_log
+                        .push(format!("Failed to read authorized_keys: {}", err));
+                    self.show_authorized_keys_panel = false;
+                }
+            }
+        }
+    }
+
+    fn selected_authorized_key(&self) -> Option<&AuthorizedKey> {
+        self.authorized_keys
+            .as_ref()?
+            .entries
+            .get(self.authorized_keys_state.selected().unwrap_or(0))
+    }
+
+    fn save_authorized_keys(&mut self, file: &AuthorizedKeysFile, action: &str, message: String) {
+        let path = file.path().display().to_string();
+        match file.save(&*self.backend) {
+            Ok(_) => {
+                self.command_log
+                    .push(format!("{}: {} -> {}", action, path, message));
+                self.authorized_keys = Some(file.clone());
+            }
+            Err(err) => self.command_log.push(format!(
+                "{}: {} -> Failed to write authorized_keys: {}",
+                action, path, err
+            )),
+        }
+    }
+
+    fn toggle_selected_authorized_key(&mut self) {
+        let (Some(mut file), Some(entry)) = (
+            self.authorized_keys.clone(),
+            self.selected_authorized_key().cloned(),
+        ) else {
+            return;
+        };
+        file.set_enabled(entry.line, !entry.enabled);
+        let (action, message) = if entry.enabled {
+            ("Disable authorized key", "commented out")
+        } else {
+            ("Enable authorized key", "enabled")
+        };
+        self.save_authorized_keys(
+            &file,
+            action,
+            format!("{} {}", entry.comment, message).trim().to_string(),
+        );
+    }
+
+    fn open_authorized_key_form(&mut self) {
+        let Some(entry) = self.selected_authorized_key().cloned() else {
+            return;
+        };
+        let value = |name: &str| {
+            entry
+                .option(name)
+                .and_then(|option| option.value.clone())
+                .unwrap_or_default()
+        };
+        self.authorized_key_from.set(value("from"));
+        self.authorized_key_command.set(value("command"));
+        self.authorized_key_expiry_time.set(value("expiry-time"));
+        self.selected_authorized_key_restrict_index = entry.option("restrict").is_some() as usize;
+        let other_options: Vec<KeyOption> = entry
+            .options
+            .iter()
+            .filter(|option| {
+                !["from", "command", "expiry-time", "restrict"]
+                    .iter()
+                    .any(|name| option.is(name))
+            })
+            .cloned()
+            .collect();
+        self.authorized_key_other_options
+            .set(authorized_keys::format_options(&other_options));
+        self.authorized_key_form_state.select(Some(0));
+        self.show_authorized_key_form = true;
+    }
+
+    fn save_authorized_key_form(&mut self) {
+        let (Some(mut file), Some(entry)) = (
+            self.authorized_keys.clone(),
+            self.selected_authorized_key().cloned(),
+        ) else {
+            return;
+        };
+
+        let mut options = Vec::new();
+        if self.selected_authorized_key_restrict_index == 1 {
+            options.push(KeyOption::new("restrict", None));
+        }
+        for (name, value) in [
+            ("from", &self.authorized_key_from),
+            ("command", &self.authorized_key_command),
+            ("expiry-time", &self.authorized_key_expiry_time),
+        ] {
+            let value = value.value().trim();
+            if !value.is_empty() {
+                options.push(KeyOption::new(name, Some(value.to_string())));
+            }
+        }
+        let result = authorized_keys::parse_options(self.authorized_key_other_options.value())
+            .and_then(|other_options| {
+                options.extend(other_options);
+                authorized_keys::validate_options(&options)
+            });
+        if let Err(err) = result {
+            self.command_log
+                .push(format!("Invalid authorized_keys options: {}", err));
+            return;
+        }
+
+        let text = authorized_keys::format_options(&options);
+        file.set_options(entry.line, options);
+        self.show_authorized_key_form = false;
+        self.save_authorized_keys(
+            &file,
+            "Edit authorized key options",
+            format!(
+                "{}: {}",
+                entry.comment,
+                if text.is_empty() { "(none)" } else { &text }
+            ),
+        );
+    }
+
+    fn authorize_selected_public_key(&mut self) {
+        let Some(selected_file) = self.selected_file_name() else {
+            return;
+        };
+        if !selected_file.ends_with(".pub") {
+            self.command_log.push(format!(
+                "Cannot authorize: {} is not a public key file of an SSH pair",
+                selected_file
+            ));
+            return;
+        }
+        let path = self.backend.ssh_dir().join(selected_file);
+        let public_key = match self.read_public_key(&path) {
+            Ok(public_key) => public_key,
+            Err(err) => {
+                self.command_log.push(err);
+                return;
+            }
+        };
+
+        let mut file = match AuthorizedKeysFile::load_user_file(&*self.backend) {
+            Ok(file) => file,
+            Err(err) => {
+                self.command_log
+                    .push(format!("Failed to read authorized_keys: {}", err));
+                return;
+            }
+        };
+        match file.add_key(&public_key) {
+            Ok(true) => self.save_authorized_keys(
+                &file,
+                "Authorize key",
+                format!("{} added", path.display()),
+            ),
+            Ok(false) => self.command_log.push(format!(
+                "Authorize key: {} -> Already in authorized_keys",
+                path.display()
+            )),
+            Err(err) => self.command_log.push(format!(
+                "Authorize key: {} -> Failed to encode key: {}",
+                path.display(),
+                err
+            )),
+        }
+    }
+
+    fn open_passphrase_form(&mut self) {
+        let Some(selected_file) = self.selected_file_name() else {
+            return;
+        };
+        let ssh_dir = self.backend.ssh_dir();
+        let key_path = ssh_dir.join(selected_file.strip_suffix(".pub").unwrap_or(selected_file));
+        if backend::read_private_key(&*self.backend, &key_path).is_err() {
+            self.command_log.push(format!(
+                "Cannot change passphrase: {} is not an OpenSSH private key",
+                key_path.display()
+            ));
+            return;
+        }
+        self.passphrase_form_key = Some(key_path);
+        self.passphrase_form_state.select(Some(0));
+        self.show_passphrase_form = true;
+    }
+
+    fn close_passphrase_form(&mut self) {
+        self.show_passphrase_form = false;
+        self.passphrase_form_key = None;
+        self.old_passphrase.zeroize();
+        self.new_passphrase.zeroize();
+        self.new_re_passphrase.zeroize();
+    }
+
+    fn change_passphrase(&mut self) {
+        let Some(key_path) = self.passphrase_form_key.clone() else {
+            return;
+        };
+        if self.jobs.is_running(CHANGE_PASSPHRASE_JOB) {
+            return;
+        }
+        if self.new_passphrase.value() != self.new_re_passphrase.value() {
+            self.command_log
+                .push("Passphrases do not match".to_string());
+            return;
+        }
+
+        let masked_old_passphrase = "*".repeat(self.old_passphrase.value().len());
+        let masked_new_passphrase = "*".repeat(self.new_passphrase.value().len());
+        self.command_log.push(format!(
+            "ssh-keygen -p -f {} -P {} -N {}",
+            key_path.display(),
+            masked_old_passphrase,
+            masked_new_passphrase
+        ));
+        let old_passphrase = Zeroizing::new(self.old_passphrase.value().to_string());
+        let new_passphrase = Zeroizing::new(self.new_passphrase.value().to_string());
+        let backend = self.backend.clone();
+        // bcrypt-pbkdf takes a noticeable moment for each of decrypting and encrypting.
+        self.jobs.spawn(CHANGE_PASSPHRASE_JOB, move || {
+            let outcome = match keys::change_passphrase(
+                &*backend,
+                &key_path,
+                &old_passphrase,
+                &new_passphrase,
+            ) {
+                Ok(()) if new_passphrase.is_empty() => PassphraseOutcome::Removed,
+                Ok(()) => PassphraseOutcome::Changed,
+                Err(ssh_key::Error::Crypto) => PassphraseOutcome::IncorrectPassphrase,
+                Err(err) => PassphraseOutcome::Failed(err.to_string()),
+            };
+            JobOutput::PassphraseChanged {
+                path: key_path,
+                outcome,
+            }
+        });
+    }
+
+    fn finish_change_passphrase(&mut self, key_path: &Path, outcome: PassphraseOutcome) {
+        match outcome {
+            PassphraseOutcome::Changed | PassphraseOutcome::Removed => {
+                let message = if outcome == PassphraseOutcome::Removed {
+                    "Passphrase removed"
+                } else {
+                    "Passphrase changed"
+                };
+                self.command_log
+                    .push(format!("{}: {}", message, key_path.display()));
+                self.inventory.invalidate(key_path);
+                if self.passphrase_form_key.as_deref() == Some(key_path) {
+                    self.close_passphrase_form();
+                }
+            }
+            PassphraseOutcome::IncorrectPassphrase => {
+                self.command_log
+                    .push("Failed to change passphrase: incorrect old passphrase".to_string());
+                if self.passphrase_form_key.as_deref() == Some(key_path) {
+                    self.old_passphrase.zeroize();
+                    self.passphrase_form_state.select(Some(0));
+                }
+            }
+            PassphraseOutcome::Failed(err) => self
+                .command_log
+                .push(format!("Failed to change passphrase: {}", err)),
+        }
+    }
+}
+
+/// A `width` x `height` rectangle centered in `area`, shrunk to fit it.
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }
+
+/// Rows `lines` take when wrapped in a bordered box `width` columns wide,
+/// borders included.
+fn bordered_text_height(lines: &[Line], width: u16) -> u16 {
+    let inner_width = usize::from(width.saturating_sub(2)).max(1);
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().div_ceil(inner_width).max(1))
+        .sum();
+    u16::try_from(rows + 2).unwrap_or(u16::MAX)
+}
+
+/// Fallback for key types the native generator does not support.
+fn create_ssh_key_with_ssh_keygen(
+    backend: &dyn SshBackend,
+    args: &[String],
+    temp_path: &Path,
+    key_path: &Path,
+    passphrase: &str,
+    log: &mut Vec<String>,
+) -> Result<(), String> {
+    let output = backend
+        .output("ssh-keygen", args)
+        .map_err(|err| format!("Failed to execute ssh-keygen: {}", err))?;
+
+    log.push(format!("ssh-keygen {}", shell_join(args)));
+    let generated = if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    };
+    finish_ssh_keygen(backend, temp_path, key_path, passphrase, generated, log)
+}
+
+/// Encrypts a key ssh-keygen wrote to `temp_path` and moves it into place.
+fn finish_ssh_keygen(
+    fs: &dyn Filesystem,
+    temp_path: &Path,
+    key_path: &Path,
+    passphrase: &str,
+    generated: Result<(), String>,
+    log: &mut Vec<String>,
+) -> Result<(), String> {
+    let temp_public_path = keys::public_key_path(temp_path);
+    let result = generated.and_then(|()| {
+        if passphrase.is_empty() {
+            return Ok(());
+        }
+        log.push(format!(
+            "Encrypt: {} -> Passphrase {}",
+            temp_path.display(),
+            "*".repeat(passphrase.len())
+        ));
+        keys::change_passphrase(fs, temp_path, "", passphrase).map_err(|err| err.to_string())
+    });
+    let result = result.and_then(|()| {
+        fs.rename(temp_path, key_path)
+            .and_then(|()| fs.rename(&temp_public_path, &keys::public_key_path(key_path)))
+            .map_err(|err| err.to_string())
+    });
+    if result.is_err() {
+        let _ = fs.remove_file(temp_path);
+        let _ = fs.remove_file(&temp_public_path);
+    }
+    result
+}
+
+/// Locks or unlocks the agent with `password`.
+fn lock_agent(
+    backend: &dyn SshBackend,
+    action: AgentLockAction,
+    password: &str,
+) -> AgentLockOutcome {
+    let result = backend.agent().and_then(|agent| match action {
+        AgentLockAction::Lock => agent.lock(password),
+        AgentLockAction::Unlock => agent.unlock(password),
+    });
+    match (action, result) {
+        (AgentLockAction::Lock, Ok(())) => AgentLockOutcome::Locked,
+        (AgentLockAction::Unlock, Ok(())) => AgentLockOutcome::Unlocked,
+        // ssh-agent only refuses to lock when it already is.
+        (AgentLockAction::Lock, Err(AgentError::Failure)) => AgentLockOutcome::AlreadyLocked,
+        (AgentLockAction::Lock, Err(err)) => {
+            AgentLockOutcome::Failed(format!("Lock agent -> Failed to lock SSH agent: {}", err))
+        }
+        (AgentLockAction::Unlock, Err(AgentError::Failure)) => {
+            // A locked agent lists no identities, so listing any means the
+            // refusal was not about the password.
+            let unlocked = backend
+                .agent()
+                .and_then(|agent| agent.list_identities())
+                .is_ok_and(|identities| !identities.is_empty());
+            if unlocked {
+                AgentLockOutcome::NotLocked
+            } else {
+                AgentLockOutcome::IncorrectPassword
+            }
+        }
+        (AgentLockAction::Unlock, Err(err)) => AgentLockOutcome::Failed(format!(
+            "Unlock agent -> Failed to unlock SSH agent: {}",
+            err
+        )),
+    }
+}
+
+/// Reads the private key at `path` and loads it into the agent, decrypting
+/// it with `passphrase` when one is given.
+fn add_to_agent(
+    backend: &dyn SshBackend,
+    path: &Path,
+    constraints: &Constraints,
+    passphrase: Option<&str>,
+) -> AgentAddOutcome {
+    let agent = match backend.agent() {
+        Ok(agent) => agent,
+        Err(err) => return AgentAddOutcome::Failed(err.to_string()),
+    };
+
+    let private_key = match backend::read_private_key(backend, path) {
+        Ok(private_key) => private_key,
+        Err(err) => {
+            return AgentAddOutcome::Failed(format!(
+                "Add to agent: {} -> Failed to read SSH private key: {}",
+                path.display(),
+                err
+            ))
+        }
+    };
+
+    let private_key = match passphrase {
+        None => {
+            match agent.contains(private_key.public_key()) {
+                Ok(true) => return AgentAddOutcome::AlreadyAdded,
+                Ok(false) => {}
+                Err(err) => return AgentAddOutcome::Failed(err.to_string()),
+            }
+            if private_key.is_encrypted() {
+                return AgentAddOutcome::NeedsPassphrase;
+            }
+            private_key
+        }
+        Some(passphrase) => match private_key.decrypt(passphrase.as_bytes()) {
+            Ok(private_key) => private_key,
+            Err(ssh_key::Error::Crypto) => return AgentAddOutcome::IncorrectPassphrase,
+            Err(err) => {
+                return AgentAddOutcome::Failed(format!(
+                    "Add to agent: {} -> Failed to decrypt SSH private key: {}",
+                    path.display(),
+                    err
+                ))
+            }
+        },
+    };
+
+    match agent.add_identity(&private_key, constraints) {
+        Ok(()) => AgentAddOutcome::Added(private_key.public_key().clone()),
+        Err(err) => AgentAddOutcome::Failed(format!(
+            "Add to agent: {} -> Failed to add SSH key to agent: {}",
+            path.display(),
+            err
+        )),
+    }
+}
+
+/// Reads the public half of the key at `path` from its `.pub` file, or from
+/// the private key when there is none.
+fn read_public_key(fs: &dyn Filesystem, path: &Path) -> Result<PublicKey, String> {
+    let public_key_path = if path.extension().is_some_and(|ext| ext == "pub") {
+        path.to_path_buf()
+    } else {
+        let mut public_key_path = path.as_os_str().to_owned();
+        public_key_path.push(".pub");
+        PathBuf::from(public_key_path)
+    };
+
+    if let Ok(public_key) = backend::read_public_key(fs, &public_key_path) {
+        return Ok(public_key);
+    }
+
+    backend::read_private_key(fs, path)
+        .map(|private_key| private_key.public_key().clone())
+        .map_err(|_| "It's not a ssh key".to_string())
+}
+
+/// The other file of a key pair: `id_rsa` for `id_rsa.pub` and the reverse.
+fn key_pair_name(file_name: &str) -> String {
+    match file_name.strip_suffix(".pub") {
+        Some(key_name) => key_name.to_string(),
+        None => format!("{}.pub", file_name),
+    }
+}
+
+/// Checks a key file name typed into the create form. Empty names are
+/// replaced by a generated one.
+fn validate_key_name(key_name: &str) -> Result<(), String> {
+    if key_name.contains(['/', '\\']) {
+        Err("must not contain path separators".to_string())
+    } else if key_name.starts_with('.') {
+        Err("must not start with a dot".to_string())
+    } else if key_name.ends_with(".pub") {
+        Err("must not end with .pub".to_string())
+    } else if key_name.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        Err("must not contain spaces".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// A rough strength hint, or `None` when the passphrase looks fine.
+fn passphrase_strength_hint(passphrase: &str) -> Option<&'static str> {
+    let classes = [
+        passphrase.chars().any(|c| c.is_lowercase()),
+        passphrase.chars().any(|c| c.is_uppercase()),
+        passphrase.chars().any(|c| c.is_numeric()),
+        passphrase.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .into_iter()
+    .filter(|&class| class)
+    .count();
+    let length = passphrase.chars().count();
+    if length == 0 {
+        Some("empty, the key will not be encrypted")
+    } else if length < 8 {
+        Some("weak, use at least 8 characters")
+    } else if length < 16 && classes < 3 {
+        Some("fair, mix cases, digits and symbols or make it longer")
+    } else {
+        None
+    }
+}
+
+/// Joins arguments for logging, quoting empty ones and ones with spaces.
+fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// ` -b <bits>` for logged key generation commands, empty for fixed-size keys.
+fn bits_arg(key_bits: Option<&str>) -> String {
+    key_bits
+        .map(|bits| format!(" -b {}", bits))
+        .unwrap_or_default()
+}
+
+fn format_lifetime(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+fn describe_constraints(constraints: &Constraints) -> String {
+    let mut parts = Vec::new();
+    if let Some(lifetime) = constraints.lifetime {
+        parts.push(format!("lifetime {}", format_lifetime(lifetime.into())));
+    }
+    if constraints.confirm {
+        parts.push("confirm".to_string());
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,506 @@
+use ssh_key::{Algorithm, PublicKey};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::backend::Filesystem;
+
+/// Options sshd accepts in authorized_keys, see `AUTHORIZED_KEYS FILE FORMAT` in sshd(8).
+pub const KNOWN_OPTIONS: &[&str] = &[
+    "agent-forwarding",
+    "cert-authority",
+    "command",
+    "environment",
+    "expiry-time",
+    "from",
+    "no-agent-forwarding",
+    "no-port-forwarding",
+    "no-pty",
+    "no-touch-required",
+    "no-user-rc",
+    "no-x11-forwarding",
+    "permitlisten",
+    "permitopen",
+    "port-forwarding",
+    "principals",
+    "pty",
+    "restrict",
+    "tunnel",
+    "user-rc",
+    "verify-required",
+    "x11-forwarding",
+];
+
+/// Options that must be given as `name="value"`.
+const VALUE_OPTIONS: &[&str] = &[
+    "command",
+    "environment",
+    "expiry-time",
+    "from",
+    "permitlisten",
+    "permitopen",
+    "principals",
+    "tunnel",
+];
+
+/// A single option in front of a key, e.g. `no-pty` or `from="10.0.0.0/8"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyOption {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl KeyOption {
+    /// Constructs a new instance of [`KeyOption`].
+    pub fn new(name: impl Into<String>, value: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// Returns whether this is the option `name`, ignoring case like sshd.
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl std::fmt::Display for KeyOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}=\"{}\"", self.name, value.replace('"', "\\\"")),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// A parsed authorized_keys line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizedKey {
+    /// Index of the line in the file.
+    pub line: usize,
+    /// False when the line has been commented out.
+    pub enabled: bool,
+    pub options: Vec<KeyOption>,
+    /// Key type as written, e.g. `ssh-ed25519`.
+    pub key_type: String,
+    /// Base64 key data as written.
+    pub key_data: String,
+    /// The decoded key, if the key type is supported.
+    pub public_key: Option<PublicKey>,
+    pub comment: String,
+}
+
+impl AuthorizedKey {
+    /// First option called `name`.
+    pub fn option(&self, name: &str) -> Option<&KeyOption> {
+        self.options.iter().find(|option| option.is(name))
+    }
+
+    /// Options as written in the file, e.g. `restrict,from="10.0.0.0/8"`.
+    pub fn options_text(&self) -> String {
+        format_options(&self.options)
+    }
+
+    /// The line without the comment-out prefix.
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        if !self.options.is_empty() {
+            line.push_str(&self.options_text());
+            line.push(' ');
+        }
+        line.push_str(&self.key_type);
+        line.push(' ');
+        line.push_str(&self.key_data);
+        if !self.comment.is_empty() {
+            line.push(' ');
+            line.push_str(&self.comment);
+        }
+        line
+    }
+}
+
+/// An authorized_keys file, keeping unparsed lines so it can be rewritten losslessly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizedKeysFile {
+    path: PathBuf,
+    lines: Vec<String>,
+    pub entries: Vec<AuthorizedKey>,
+}
+
+impl AuthorizedKeysFile {
+    /// Reads `~/.ssh/authorized_keys`.
+    pub fn load_user_file(fs: &dyn Filesystem) -> io::Result<Self> {
+        Self::load(fs, &fs.ssh_dir().join("authorized_keys"))
+    }
+
+    /// Reads an authorized_keys file. A missing file yields an empty list.
+    pub fn load(fs: &dyn Filesystem, path: &Path) -> io::Result<Self> {
+        let content = match fs.read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self::parse(path, &content))
+    }
+
+    /// Parses authorized_keys content.
+    pub fn parse(path: &Path, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let entries = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| parse_line(index, line))
+            .collect();
+        Self {
+            path: path.to_path_buf(),
+            lines,
+            entries,
+        }
+    }
+
+    /// Path the file is saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entry for `public_key`, enabled or not.
+    pub fn find_key(&self, public_key: &PublicKey) -> Option<&AuthorizedKey> {
+        self.entries.iter().find(|entry| {
+            entry
+                .public_key
+                .as_ref()
+                .is_some_and(|key| key.key_data() == public_key.key_data())
+        })
+    }
+
+    /// Appends a key without options. Returns false if the key is already listed.
+    pub fn add_key(&mut self, public_key: &PublicKey) -> Result<bool, String> {
+        if self.find_key(public_key).is_some() {
+            return Ok(false);
+        }
+        let line = public_key.to_openssh().map_err(|err| err.to_string())?;
+        self.lines.push(line);
+        self.reparse();
+        Ok(true)
+    }
+
+    /// Comments out or restores the entry on `line`.
+    pub fn set_enabled(&mut self, line: usize, enabled: bool) {
+        let Some(entry) = self.entries.iter().find(|entry| entry.line == line) else {
+            return;
+        };
+        self.lines[line] = if enabled {
+            entry.to_line()
+        } else {
+            format!("# {}", entry.to_line())
+        };
+        self.reparse();
+    }
+
+    /// Replaces the options of the entry on `line`.
+    pub fn set_options(&mut self, line: usize, options: Vec<KeyOption>) {
+        let Some(entry) = self.entries.iter().find(|entry| entry.line == line) else {
+            return;
+        };
+        let mut entry = entry.clone();
+        entry.options = options;
+        self.lines[line] = if entry.enabled {
+            entry.to_line()
+        } else {
+            format!("# {}", entry.to_line())
+        };
+        self.reparse();
+    }
+
+    /// Writes the file with mode 0600, keeping the previous version as
+    /// `authorized_keys.old`. Returns the backup path, if a backup was made.
+    pub fn save(&self, fs: &dyn Filesystem) -> io::Result<Option<PathBuf>> {
+        let backup = if fs.exists(&self.path) {
+            let mut backup = self.path.as_os_str().to_owned();
+            backup.push(".old");
+            let backup = PathBuf::from(backup);
+            fs.copy(&self.path, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        let mut content = self.lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        fs.write(&self.path, content.as_bytes(), 0o600)?;
+        Ok(backup)
+    }
+
+    fn reparse(&mut self) {
+        let content = self.lines.join("\n");
+        *self = Self::parse(&self.path, &content);
+    }
+}
+
+fn parse_line(index: usize, line: &str) -> Option<AuthorizedKey> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    // A commented-out line only counts as a disabled entry if it still holds a valid key.
+    let (enabled, line) = match line.strip_prefix('#') {
+        Some(rest) => (false, rest.trim_start()),
+        None => (true, line),
+    };
+
+    let (first, rest) = split_token(line);
+    let (options, rest) = if is_key_type(first) {
+        (Vec::new(), line)
+    } else {
+        (parse_options(first).ok()?, rest)
+    };
+
+    let mut tokens = rest.split_whitespace();
+    let key_type = tokens.next()?.to_string();
+    let key_data = tokens.next()?.to_string();
+    let comment = tokens.collect::<Vec<_>>().join(" ");
+    let public_key = PublicKey::from_openssh(&format!("{} {}", key_type, key_data)).ok();
+    if !enabled && public_key.is_none() {
+        return None;
+    }
+
+    Some(AuthorizedKey {
+        line: index,
+        enabled,
+        options,
+        key_type,
+        key_data,
+        public_key,
+        comment,
+    })
+}
+
+fn is_key_type(token: &str) -> bool {
+    Algorithm::from_str(token).is_ok()
+        || token.starts_with("ssh-")
+        || token.starts_with("ecdsa-")
+        || token.starts_with("sk-")
+}
+
+/// Splits off the first whitespace-separated token, honouring double quotes.
+fn split_token(line: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                return (&line[..index], line[index..].trim_start());
+            }
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+/// Parses a comma-separated option list, e.g. `restrict,command="uptime"`.
+pub fn parse_options(text: &str) -> Result<Vec<KeyOption>, String> {
+    let mut options = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == ',' || c == '=' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("empty option name".to_string());
+        }
+
+        let value = if chars.peek() == Some(&'=') {
+            chars.next();
+            if chars.next() != Some('"') {
+                return Err(format!("value of {} must be in double quotes", name));
+            }
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') if chars.peek() == Some(&'"') => {
+                        value.push('"');
+                        chars.next();
+                    }
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated quote in {}", name)),
+                }
+            }
+            Some(value)
+        } else {
+            None
+        };
+        options.push(KeyOption { name, value });
+
+        match chars.next() {
+            Some(',') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            None => break,
+            Some(c) => return Err(format!("unexpected {:?} after an option", c)),
+        }
+    }
+    Ok(options)
+}
+
+/// Formats options back into the comma-separated form.
+pub fn format_options(options: &[KeyOption]) -> String {
+    options
+        .iter()
+        .map(KeyOption::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Checks options the way sshd would before accepting the key line.
+pub fn validate_options(options: &[KeyOption]) -> Result<(), String> {
+    for option in options {
+        let name = option.name.to_ascii_lowercase();
+        if !KNOWN_OPTIONS.contains(&name.as_str()) {
+            return Err(format!("unknown option {}", option.name));
+        }
+        let needs_value = VALUE_OPTIONS.contains(&name.as_str());
+        match &option.value {
+            None if needs_value => return Err(format!("{} needs a value", option.name)),
+            Some(_) if !needs_value => {
+                return Err(format!("{} does not take a value", option.name))
+            }
+            _ => {}
+        }
+        if name == "expiry-time" {
+            let value = option.value.as_deref().unwrap_or_default();
+            let value = value.strip_suffix('Z').unwrap_or(value);
+            if ![8, 12, 14].contains(&value.len()) || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(format!(
+                    "expiry-time {:?} must be YYYYMMDD[HHMM[SS]]",
+                    option.value.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAILZZKV/QtdMib2mtHJCqm7dHfRmFk7GSIi/PNbsw0bYW";
+    const BOB: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAfuCHKVTjquxvt6CM6tdG4SLp1Btn/nOeHHE5UOzRdf";
+
+    fn parse(content: &str) -> AuthorizedKeysFile {
+        AuthorizedKeysFile::parse(Path::new("/home/user/.ssh/authorized_keys"), content)
+    }
+
+    fn option(name: &str, value: Option<&str>) -> KeyOption {
+        KeyOption::new(name, value.map(str::to_string))
+    }
+
+    #[test]
+    fn options_round_trip_with_escaped_quotes() {
+        let text =
+            r#"restrict,command="echo \"hello, world\"", from="10.0.0.0/8,!10.0.0.1",no-pty"#;
+        let options = parse_options(text).unwrap();
+        assert_eq!(
+            options,
+            [
+                option("restrict", None),
+                option("command", Some(r#"echo "hello, world""#)),
+                option("from", Some("10.0.0.0/8,!10.0.0.1")),
+                option("no-pty", None),
+            ]
+        );
+
+        let formatted = format_options(&options);
+        assert_eq!(
+            formatted,
+            r#"restrict,command="echo \"hello, world\"",from="10.0.0.0/8,!10.0.0.1",no-pty"#
+        );
+        assert_eq!(parse_options(&formatted).unwrap(), options);
+
+        let file = parse(&format!("{} {} alice@laptop\n", formatted, ALICE));
+        assert_eq!(file.entries[0].options, options);
+        assert_eq!(file.entries[0].comment, "alice@laptop");
+    }
+
+    #[test]
+    fn rejects_malformed_options() {
+        assert!(parse_options("command=uptime").is_err());
+        assert!(parse_options(r#"command="uptime"#).is_err());
+        assert!(parse_options(",no-pty").is_err());
+        assert!(parse_options(r#"command="uptime"x"#).is_err());
+        assert_eq!(parse_options("").unwrap(), []);
+    }
+
+    #[test]
+    fn set_enabled_comments_out_and_restores_lines() {
+        let content =
+            format!("restrict {ALICE} alice@laptop\n# {BOB} bob@desktop\n# a plain comment\n");
+        let mut file = parse(&content);
+        assert_eq!(file.entries.len(), 2);
+        assert!(file.entries[0].enabled);
+        assert!(!file.entries[1].enabled);
+        assert_eq!(file.entries[1].line, 1);
+
+        file.set_enabled(0, false);
+        file.set_enabled(1, true);
+        assert_eq!(
+            file.lines,
+            [
+                format!("# restrict {ALICE} alice@laptop"),
+                format!("{BOB} bob@desktop"),
+                "# a plain comment".to_string(),
+            ]
+        );
+        assert!(!file.entries[0].enabled);
+        assert_eq!(file.entries[0].options, [option("restrict", None)]);
+        assert!(file.entries[1].enabled);
+
+        file.set_enabled(0, true);
+        file.set_enabled(1, false);
+        assert_eq!(file.lines.join("\n") + "\n", content);
+        // Plain comments are not entries and cannot be enabled.
+        file.set_enabled(2, true);
+        assert_eq!(file.lines[2], "# a plain comment");
+    }
+
+    #[test]
+    fn validate_options_checks_names_and_values() {
+        let valid = [
+            option("restrict", None),
+            option("expiry-time", Some("20261231")),
+            option("EXPIRY-TIME", Some("202612312359Z")),
+            option("from", Some("10.0.0.0/8")),
+        ];
+        assert_eq!(validate_options(&valid), Ok(()));
+
+        for expiry in ["2026-12-31", "2026123", "20261231235", "tomorrow"] {
+            assert!(
+                validate_options(&[option("expiry-time", Some(expiry))]).is_err(),
+                "{expiry}"
+            );
+        }
+        assert_eq!(
+            validate_options(&[option("no-such-option", None)]),
+            Err("unknown option no-such-option".to_string())
+        );
+        assert!(validate_options(&[option("command", None)]).is_err());
+        assert!(validate_options(&[option("no-pty", Some("yes"))]).is_err());
+    }
+}
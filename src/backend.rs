@@ -0,0 +1,305 @@
+use arboard::Clipboard;
+use notify::{EventKind, RecursiveMode, Watcher};
+use ssh_key::{PrivateKey, PublicKey};
+use std::fs;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::agent::{self, AgentClient, AgentError, Constraints, Identity};
+use crate::event::TerminalEvent;
+
+#[cfg(test)]
+mod fake;
+
+#[cfg(test)]
+pub use fake::{FakeBackend, FakeFile};
+
+/// How long file events are collected into one [`TerminalEvent::FilesChanged`],
+/// so an editor's save or a `git pull` reloads the files once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Keeps a watch started by [`SshBackend::watch`] running until dropped.
+pub type WatchGuard = Box<dyn Send>;
+
+/// File access used by the app and the file modules, so they can run
+/// against an in-memory tree.
+pub trait Filesystem: Send + Sync {
+    /// Home directory of the user.
+    fn home_dir(&self) -> Option<PathBuf>;
+
+    /// `~/.ssh`, or `.ssh` in the working directory if there is no home.
+    fn ssh_dir(&self) -> PathBuf {
+        self.home_dir().unwrap_or_default().join(".ssh")
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Paths of the entries of a directory, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn exists(&self, path: &Path) -> bool;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Last modification time of a file.
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+
+    /// Creates or truncates a file. `mode` only applies to a new file.
+    fn write(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()>;
+
+    /// Creates a file, failing with [`io::ErrorKind::AlreadyExists`] if
+    /// there is one.
+    fn create_new(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()>;
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Moves a file to the trash, where it can be restored from.
+    fn trash(&self, path: &Path) -> Result<(), String>;
+}
+
+/// Reads a private key in OpenSSH format.
+pub fn read_private_key(fs: &dyn Filesystem, path: &Path) -> Result<PrivateKey, ssh_key::Error> {
+    PrivateKey::from_openssh(fs.read_to_string(path)?)
+}
+
+/// Reads a public key in OpenSSH format.
+pub fn read_public_key(fs: &dyn Filesystem, path: &Path) -> Result<PublicKey, ssh_key::Error> {
+    PublicKey::from_openssh(fs.read_to_string(path)?.trim())
+}
+
+/// Writes a file through a temporary file next to it that is renamed over
+/// the original, so a failed write never leaves it truncated. A stale
+/// temporary file is removed first, as it would keep its own mode.
+pub fn replace_file(
+    fs: &dyn Filesystem,
+    path: &Path,
+    contents: &[u8],
+    mode: u32,
+) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = Path::new(&temp_path);
+    match fs.remove_file(temp_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    fs.create_new(temp_path, contents, mode)?;
+    if let Err(err) = fs.rename(temp_path, path) {
+        let _ = fs.remove_file(temp_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// An ssh-agent connection.
+pub trait Agent {
+    fn list_identities(&self) -> Result<Vec<Identity>, AgentError>;
+
+    /// Returns whether the agent holds `public_key`.
+    fn contains(&self, public_key: &PublicKey) -> Result<bool, AgentError> {
+        let key_blob = agent::public_key_blob(public_key)?;
+        Ok(self
+            .list_identities()?
+            .iter()
+            .any(|identity| identity.key_blob == key_blob))
+    }
+
+    fn add_identity(
+        &self,
+        private_key: &PrivateKey,
+        constraints: &Constraints,
+    ) -> Result<(), AgentError>;
+
+    fn remove_identity(&self, key_blob: &[u8]) -> Result<(), AgentError>;
+
+    fn remove_all_identities(&self) -> Result<(), AgentError>;
+
+    fn lock(&self, password: &str) -> Result<(), AgentError>;
+
+    fn unlock(&self, password: &str) -> Result<(), AgentError>;
+}
+
+impl Agent for AgentClient {
+    fn list_identities(&self) -> Result<Vec<Identity>, AgentError> {
+        AgentClient::list_identities(self)
+    }
+
+    fn add_identity(
+        &self,
+        private_key: &PrivateKey,
+        constraints: &Constraints,
+    ) -> Result<(), AgentError> {
+        self.add_identity_constrained(private_key, constraints)
+    }
+
+    fn remove_identity(&self, key_blob: &[u8]) -> Result<(), AgentError> {
+        AgentClient::remove_identity(self, key_blob)
+    }
+
+    fn remove_all_identities(&self) -> Result<(), AgentError> {
+        AgentClient::remove_all_identities(self)
+    }
+
+    fn lock(&self, password: &str) -> Result<(), AgentError> {
+        AgentClient::lock(self, password)
+    }
+
+    fn unlock(&self, password: &str) -> Result<(), AgentError> {
+        AgentClient::unlock(self, password)
+    }
+}
+
+/// Everything [`crate::app::App`] does outside its own state: files, the
+/// agent, the clipboard and external commands.
+pub trait SshBackend: Filesystem {
+    /// Connects to the agent of the session.
+    fn agent(&self) -> Result<Box<dyn Agent>, AgentError>;
+
+    fn set_clipboard(&self, text: &str) -> Result<(), String>;
+
+    /// Runs a command without input and captures its output.
+    fn output(&self, program: &str, args: &[String]) -> io::Result<Output>;
+
+    /// Runs a command on the terminal of the app and waits for it to exit.
+    fn run_interactive(&self, program: &str, args: &[String]) -> io::Result<ExitStatus>;
+
+    /// Sends [`TerminalEvent::FilesChanged`] through `sender` whenever an
+    /// entry of `dir` changes, for as long as the guard lives.
+    fn watch(&self, dir: &Path, sender: mpsc::Sender<TerminalEvent>) -> Result<WatchGuard, String>;
+}
+
+/// The local machine.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealBackend;
+
+impl Filesystem for RealBackend {
+    fn home_dir(&self) -> Option<PathBuf> {
+        dirs::home_dir()
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        set_mode(&mut options, mode);
+        options.open(path)?.write_all(contents)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        set_mode(&mut options, mode);
+        options.open(path)?.write_all(contents)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn trash(&self, path: &Path) -> Result<(), String> {
+        trash::delete(path).map_err(|err| err.to_string())
+    }
+}
+
+/// Sets the permissions a new file is created with. Other platforms have no
+/// mode bits and keep their defaults.
+#[cfg(unix)]
+fn set_mode(options: &mut fs::OpenOptions, mode: u32) {
+    options.mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_mode(_options: &mut fs::OpenOptions, _mode: u32) {}
+
+impl SshBackend for RealBackend {
+    fn agent(&self) -> Result<Box<dyn Agent>, AgentError> {
+        Ok(Box::new(AgentClient::from_env()?))
+    }
+
+    fn set_clipboard(&self, text: &str) -> Result<(), String> {
+        Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .map_err(|err| err.to_string())
+    }
+
+    fn output(&self, program: &str, args: &[String]) -> io::Result<Output> {
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+    }
+
+    fn run_interactive(&self, program: &str, args: &[String]) -> io::Result<ExitStatus> {
+        Command::new(program).args(args).status()
+    }
+
+    fn watch(&self, dir: &Path, sender: mpsc::Sender<TerminalEvent>) -> Result<WatchGuard, String> {
+        let (changes, changed) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                // The app reads the files it shows, which must not count as a change.
+                if event.is_ok_and(|event| !matches!(event.kind, EventKind::Access(_))) {
+                    let _ = changes.send(());
+                }
+            })
+            .map_err(|err| err.to_string())?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| err.to_string())?;
+
+        // Ends once the watcher, and with it the sending half, is dropped.
+        thread::spawn(move || {
+            while changed.recv().is_ok() {
+                let deadline = Instant::now() + WATCH_DEBOUNCE;
+                while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                    if changed.recv_timeout(timeout).is_err() {
+                        break;
+                    }
+                }
+                if sender.send(TerminalEvent::FilesChanged).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Box::new(watcher))
+    }
+}
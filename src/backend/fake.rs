@@ -0,0 +1,404 @@
+use ssh_key::PrivateKey;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Agent, Filesystem, SshBackend, WatchGuard};
+use crate::agent::{self, AgentError, Constraints, Identity};
+use crate::event::TerminalEvent;
+
+/// A file held by [`FakeBackend`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FakeFile {
+    pub contents: Vec<u8>,
+    pub mode: u32,
+    pub modified: SystemTime,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    files: BTreeMap<PathBuf, FakeFile>,
+    /// Directories that ever held a file. Like on disk, they stay when their
+    /// files are removed.
+    dirs: BTreeSet<PathBuf>,
+    trash: Vec<PathBuf>,
+    /// `None` when no agent is running.
+    agent: Option<FakeAgentState>,
+    clipboard: Option<String>,
+    commands: Vec<Vec<String>>,
+    /// Seconds since the epoch of the last write, advanced by every write.
+    clock: u64,
+    reads: usize,
+    /// Watched directories, notified of changes to their entries.
+    watchers: Vec<(PathBuf, mpsc::Sender<TerminalEvent>)>,
+}
+
+impl FakeState {
+    fn insert_file(&mut self, path: PathBuf, contents: &[u8], mode: u32) {
+        self.clock += 1;
+        let file = FakeFile {
+            contents: contents.to_vec(),
+            mode,
+            modified: UNIX_EPOCH + Duration::from_secs(self.clock),
+        };
+        self.changed(&path);
+        self.insert(path, file);
+    }
+
+    fn insert(&mut self, path: PathBuf, file: FakeFile) {
+        self.dirs
+            .extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        self.files.insert(path, file);
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Option<FakeFile> {
+        let file = self.files.remove(path)?;
+        self.changed(path);
+        Some(file)
+    }
+
+    fn changed(&self, path: &Path) {
+        for (dir, sender) in &self.watchers {
+            if path.parent() == Some(dir.as_path()) {
+                let _ = sender.send(TerminalEvent::FilesChanged);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeAgentState {
+    identities: Vec<(Identity, Constraints)>,
+    lock_password: Option<String>,
+}
+
+/// An in-memory [`SshBackend`] for tests.
+///
+/// Clones share their state, so a test can keep one to inspect what the app
+/// did with the other. Commands are recorded and succeed without output.
+/// Writes notify watchers of their directory, and watches never stop.
+#[derive(Clone, Debug)]
+pub struct FakeBackend {
+    home: PathBuf,
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeBackend {
+    /// Constructs an empty home at `/home/user` with a running agent.
+    pub fn new() -> Self {
+        let state = FakeState {
+            agent: Some(FakeAgentState::default()),
+            ..FakeState::default()
+        };
+        Self {
+            home: PathBuf::from("/home/user"),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Stops the agent, so connecting fails like without `SSH_AUTH_SOCK`.
+    pub fn without_agent(self) -> Self {
+        self.state().agent = None;
+        self
+    }
+
+    /// Adds a file to `~/.ssh` with mode 0600.
+    pub fn with_ssh_file(self, name: &str, contents: impl AsRef<[u8]>) -> Self {
+        let path = self.ssh_dir().join(name);
+        self.state().insert_file(path, contents.as_ref(), 0o600);
+        self
+    }
+
+    /// The file at `path`, if there is one.
+    pub fn file(&self, path: &Path) -> Option<FakeFile> {
+        self.state().files.get(path).cloned()
+    }
+
+    /// Content of `~/.ssh/<name>`, if it exists and is UTF-8.
+    pub fn ssh_file(&self, name: &str) -> Option<String> {
+        let file = self.file(&self.ssh_dir().join(name))?;
+        String::from_utf8(file.contents).ok()
+    }
+
+    /// Paths of every file, sorted.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.state().files.keys().cloned().collect()
+    }
+
+    /// Paths moved to the trash, oldest first.
+    pub fn trashed(&self) -> Vec<PathBuf> {
+        self.state().trash.clone()
+    }
+
+    /// Identities held by the agent, with the constraints they were added with.
+    pub fn agent_identities(&self) -> Vec<(Identity, Constraints)> {
+        self.state()
+            .agent
+            .as_ref()
+            .map(|agent| agent.identities.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the agent is locked.
+    pub fn agent_locked(&self) -> bool {
+        self.state()
+            .agent
+            .as_ref()
+            .is_some_and(|agent| agent.lock_password.is_some())
+    }
+
+    pub fn clipboard(&self) -> Option<String> {
+        self.state().clipboard.clone()
+    }
+
+    /// Number of files read so far.
+    pub fn reads(&self) -> usize {
+        self.state().reads
+    }
+
+    /// Commands run so far, each as the program followed by its arguments.
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.state().commands.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn record_command(&self, program: &str, args: &[String]) {
+        let mut command = vec![program.to_string()];
+        command.extend_from_slice(args);
+        self.state().commands.push(command);
+    }
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: No such file or directory", path.display()),
+    )
+}
+
+impl Filesystem for FakeBackend {
+    fn home_dir(&self) -> Option<PathBuf> {
+        Some(self.home.clone())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.state().reads += 1;
+        let file = self.file(path).ok_or_else(|| not_found(path))?;
+        String::from_utf8(file.contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let state = self.state();
+        let mut entries: Vec<PathBuf> = state
+            .files
+            .keys()
+            .filter_map(|file| {
+                let name = file.strip_prefix(path).ok()?.components().next()?;
+                Some(path.join(name))
+            })
+            .collect();
+        entries.dedup();
+        if entries.is_empty() {
+            return Err(not_found(path));
+        }
+        Ok(entries)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let state = self.state();
+        state.files.contains_key(path) || state.dirs.contains(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.state().files.contains_key(path)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        let file = self.file(path).ok_or_else(|| not_found(path))?;
+        Ok(file.modified)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+        let mut state = self.state();
+        let mode = state.files.get(path).map_or(mode, |file| file.mode);
+        state.insert_file(path.to_path_buf(), contents, mode);
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+        if self.is_file(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{}: File exists", path.display()),
+            ));
+        }
+        self.write(path, contents, mode)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let file = self.file(from).ok_or_else(|| not_found(from))?;
+        self.write(to, &file.contents, file.mode)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.state();
+        let file = state.remove_file(from).ok_or_else(|| not_found(from))?;
+        state.changed(to);
+        state.insert(to.to_path_buf(), file);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.state()
+            .remove_file(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn trash(&self, path: &Path) -> Result<(), String> {
+        self.remove_file(path).map_err(|err| err.to_string())?;
+        self.state().trash.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+impl SshBackend for FakeBackend {
+    fn agent(&self) -> Result<Box<dyn Agent>, AgentError> {
+        if self.state().agent.is_none() {
+            return Err(AgentError::NoSocket);
+        }
+        Ok(Box::new(self.clone()))
+    }
+
+    fn set_clipboard(&self, text: &str) -> Result<(), String> {
+        self.state().clipboard = Some(text.to_string());
+        Ok(())
+    }
+
+    fn output(&self, program: &str, args: &[String]) -> io::Result<Output> {
+        self.record_command(program, args);
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn run_interactive(&self, program: &str, args: &[String]) -> io::Result<ExitStatus> {
+        self.record_command(program, args);
+        Ok(ExitStatus::default())
+    }
+
+    fn watch(&self, dir: &Path, sender: mpsc::Sender<TerminalEvent>) -> Result<WatchGuard, String> {
+        self.state().watchers.push((dir.to_path_buf(), sender));
+        Ok(Box::new(()))
+    }
+}
+
+impl FakeBackend {
+    /// Runs `f` on the agent, failing like a real agent when it is locked.
+    fn with_agent<T>(
+        &self,
+        f: impl FnOnce(&mut FakeAgentState) -> Result<T, AgentError>,
+    ) -> Result<T, AgentError> {
+        let mut state = self.state();
+        let agent = state.agent.as_mut().ok_or(AgentError::NoSocket)?;
+        f(agent)
+    }
+}
+
+impl Agent for FakeBackend {
+    fn list_identities(&self) -> Result<Vec<Identity>, AgentError> {
+        self.with_agent(|agent| {
+            if agent.lock_password.is_some() {
+                return Ok(Vec::new());
+            }
+            Ok(agent
+                .identities
+                .iter()
+                .map(|(identity, _)| identity.clone())
+                .collect())
+        })
+    }
+
+    fn add_identity(
+        &self,
+        private_key: &PrivateKey,
+        constraints: &Constraints,
+    ) -> Result<(), AgentError> {
+        let key_blob = agent::public_key_blob(private_key.public_key())?;
+        self.with_agent(|agent| {
+            if agent.lock_password.is_some() {
+                return Err(AgentError::Failure);
+            }
+            agent
+                .identities
+                .retain(|(identity, _)| identity.key_blob != key_blob);
+            let identity = Identity {
+                key_blob,
+                comment: private_key.comment().to_string(),
+            };
+            agent.identities.push((identity, *constraints));
+            Ok(())
+        })
+    }
+
+    fn remove_identity(&self, key_blob: &[u8]) -> Result<(), AgentError> {
+        self.with_agent(|agent| {
+            let index = agent
+                .identities
+                .iter()
+                .position(|(identity, _)| identity.key_blob == key_blob);
+            match index {
+                Some(index) if agent.lock_password.is_none() => {
+                    agent.identities.remove(index);
+                    Ok(())
+                }
+                _ => Err(AgentError::Failure),
+            }
+        })
+    }
+
+    fn remove_all_identities(&self) -> Result<(), AgentError> {
+        self.with_agent(|agent| {
+            if agent.lock_password.is_some() {
+                return Err(AgentError::Failure);
+            }
+            agent.identities.clear();
+            Ok(())
+        })
+    }
+
+    fn lock(&self, password: &str) -> Result<(), AgentError> {
+        self.with_agent(|agent| {
+            if agent.lock_password.is_some() {
+                return Err(AgentError::Failure);
+            }
+            agent.lock_password = Some(password.to_string());
+            Ok(())
+        })
+    }
+
+    fn unlock(&self, password: &str) -> Result<(), AgentError> {
+        self.with_agent(|agent| {
+            if agent.lock_password.as_deref() != Some(password) {
+                return Err(AgentError::Failure);
+            }
+            agent.lock_password = None;
+            Ok(())
+        })
+    }
+}
@@ -1,9 +1,16 @@
 use color_eyre::Result;
 use ratatui::crossterm::event::{self, Event, KeyEvent, KeyEventKind, MouseEvent};
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::jobs::JobResult;
+
+/// Longest time the handler thread blocks in `poll`, so a pause request is
+/// noticed quickly.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Terminal events.
 #[derive(Clone, Debug, PartialEq)]
 pub enum TerminalEvent {
@@ -15,6 +22,12 @@ pub enum TerminalEvent {
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// Text pasted while bracketed paste is enabled.
+    Paste(String),
+    /// A background job finished.
+    Job(JobResult),
+    /// An entry of the watched `~/.ssh` was created, changed or removed.
+    FilesChanged,
 }
 
 /// Terminal event handler.
@@ -27,8 +40,12 @@ pub struct EventHandler {
     pub sender: mpsc::Sender<TerminalEvent>,
     /// Event receiver channel.
     receiver: mpsc::Receiver<TerminalEvent>,
-    /// Event handler thread.
-    handler: thread::JoinHandle<()>,
+    /// Event handler thread, `None` when the terminal is not read.
+    handler: Option<thread::JoinHandle<()>>,
+    /// Set to ask the handler thread to stop reading the terminal.
+    paused: Arc<AtomicBool>,
+    /// Set by the handler thread once it has stopped reading the terminal.
+    parked: Arc<AtomicBool>,
 }
 
 impl EventHandler {
@@ -36,14 +53,27 @@ impl EventHandler {
     pub fn new() -> Self {
         let tick_rate = Duration::from_millis(5000);
         let (sender, receiver) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let parked = Arc::new(AtomicBool::new(false));
         let handler = {
             let sender = sender.clone();
+            let paused = paused.clone();
+            let parked = parked.clone();
             thread::spawn(move || {
                 let mut last_tick = Instant::now();
                 loop {
+                    if paused.load(Ordering::SeqCst) {
+                        parked.store(true, Ordering::SeqCst);
+                        thread::sleep(POLL_INTERVAL);
+                        last_tick = Instant::now();
+                        continue;
+                    }
+                    parked.store(false, Ordering::SeqCst);
+
                     let timeout = tick_rate
                         .checked_sub(last_tick.elapsed())
-                        .unwrap_or(tick_rate);
+                        .unwrap_or(tick_rate)
+                        .min(POLL_INTERVAL);
                     if event::poll(timeout).expect("failed to poll new events") {
                         match event::read().expect("unable to read event") {
                             Event::Key(e) => {
@@ -57,7 +87,7 @@ impl EventHandler {
                             Event::Resize(w, h) => sender.send(TerminalEvent::Resize(w, h)),
                             Event::FocusGained => Ok(()),
                             Event::FocusLost => Ok(()),
-                            Event::Paste(_) => unimplemented!(),
+                            Event::Paste(text) => sender.send(TerminalEvent::Paste(text)),
                         }
                         .expect("failed to send terminal event")
                     }
@@ -75,10 +105,42 @@ impl EventHandler {
             tick_rate,
             sender,
             receiver,
-            handler,
+            handler: Some(handler),
+            paused,
+            parked,
         }
     }
 
+    /// Constructs an [`EventHandler`] that does not read the terminal, so the
+    /// app can be driven without one. Only events sent through `sender`
+    /// arrive, and pausing returns immediately.
+    pub fn detached() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            tick_rate: Duration::from_millis(5000),
+            sender,
+            receiver,
+            handler: None,
+            paused: Arc::new(AtomicBool::new(false)),
+            parked: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Stops reading terminal events, so a child process can own the TTY.
+    ///
+    /// Blocks until the handler thread has finished its current poll.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while !self.parked.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Resumes reading terminal events after [`EventHandler::pause`].
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
@@ -87,6 +149,16 @@ impl EventHandler {
     pub fn next(&self) -> Result<TerminalEvent> {
         Ok(self.receiver.recv()?)
     }
+
+    /// Like [`EventHandler::next`], but gives up after `timeout` and returns
+    /// `None`, so the screen can be redrawn while jobs run.
+    pub fn next_timeout(&self, timeout: Duration) -> Result<Option<TerminalEvent>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
 }
 
 impl Default for EventHandler {
@@ -0,0 +1,451 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use zeroize::Zeroize;
+
+/// A text input with a cursor, single-line unless constructed with
+/// [`TextInput::multiline`].
+///
+/// The cursor is a byte offset that always sits on a grapheme boundary, so
+/// editing never splits a character made of several code points.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextInput {
+    value: String,
+    cursor: usize,
+    /// Shown as one `*` per character and zeroized when cleared.
+    secret: bool,
+    /// Keeps line breaks, which single-line inputs turn into spaces.
+    multiline: bool,
+}
+
+impl TextInput {
+    /// Constructs an empty [`TextInput`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an empty [`TextInput`] for passphrases.
+    pub fn secret() -> Self {
+        Self {
+            secret: true,
+            ..Self::default()
+        }
+    }
+
+    /// Constructs an empty [`TextInput`] for text with one entry per line.
+    pub fn multiline() -> Self {
+        Self {
+            multiline: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Replaces the text and moves the cursor to the end.
+    pub fn set(&mut self, value: impl Into<String>) {
+        self.clear();
+        self.value = value.into();
+        self.cursor = self.value.len();
+    }
+
+    /// Empties the input, wiping the old text of secret inputs.
+    pub fn clear(&mut self) {
+        if self.secret {
+            self.value.zeroize();
+        } else {
+            self.value.clear();
+        }
+        self.cursor = 0;
+    }
+
+    /// Text as shown on screen.
+    pub fn display_text(&self) -> String {
+        if self.secret {
+            "*".repeat(self.value.graphemes(true).count())
+        } else {
+            self.value.clone()
+        }
+    }
+
+    /// Column of the cursor within its line of [`TextInput::display_text`].
+    pub fn cursor_column(&self) -> usize {
+        let before = &self.value[self.line_start()..self.cursor];
+        if self.secret {
+            before.graphemes(true).count()
+        } else {
+            before.width()
+        }
+    }
+
+    /// Line of the cursor, counting from 0.
+    pub fn cursor_line(&self) -> usize {
+        self.value[..self.cursor].matches('\n').count()
+    }
+
+    /// Horizontal scroll that keeps the cursor inside a field `width` columns wide.
+    pub fn scroll_offset(&self, width: u16) -> u16 {
+        let column = u16::try_from(self.cursor_column()).unwrap_or(u16::MAX);
+        column.saturating_sub(width.saturating_sub(1))
+    }
+
+    /// Vertical scroll that keeps the cursor inside a field `height` lines high.
+    pub fn line_scroll_offset(&self, height: u16) -> u16 {
+        let line = u16::try_from(self.cursor_line()).unwrap_or(u16::MAX);
+        line.saturating_sub(height.saturating_sub(1))
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.reserve(c.len_utf8());
+        self.value.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Starts a new line, if the input is multiline.
+    pub fn insert_newline(&mut self) {
+        if self.multiline {
+            self.insert('\n');
+        }
+    }
+
+    /// Inserts pasted text. Line breaks become spaces unless the input is
+    /// multiline, tabs become spaces, a trailing line break is dropped and
+    /// other control characters are skipped.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.trim_end_matches(['\r', '\n']).replace("\r\n", "\n");
+        let mut text: String = text
+            .chars()
+            .filter_map(|c| match c {
+                '\r' | '\n' if self.multiline => Some('\n'),
+                '\r' | '\n' | '\t' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        self.reserve(text.len());
+        self.value.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+        text.zeroize();
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.previous_boundary();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_boundary();
+    }
+
+    /// Moves to the start of the line.
+    pub fn move_home(&mut self) {
+        self.cursor = self.line_start();
+    }
+
+    /// Moves to the end of the line.
+    pub fn move_end(&mut self) {
+        self.cursor = self.line_end();
+    }
+
+    /// Moves to the previous line, keeping the column where it can.
+    pub fn move_up(&mut self) {
+        let line_start = self.line_start();
+        if line_start == 0 {
+            return;
+        }
+        let graphemes = self.value[line_start..self.cursor].graphemes(true).count();
+        self.cursor = line_start - 1;
+        self.cursor = self.line_start();
+        self.move_right_within_line(graphemes);
+    }
+
+    /// Moves to the next line, keeping the column where it can.
+    pub fn move_down(&mut self) {
+        let line_end = self.line_end();
+        if line_end == self.value.len() {
+            return;
+        }
+        let graphemes = self.value[self.line_start()..self.cursor]
+            .graphemes(true)
+            .count();
+        self.cursor = line_end + 1;
+        self.move_right_within_line(graphemes);
+    }
+
+    /// Moves to the start of the previous word.
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.previous_word_start();
+    }
+
+    /// Moves past the end of the next word.
+    pub fn move_word_right(&mut self) {
+        let rest = &self.value[self.cursor..];
+        let word_start = rest.len() - rest.trim_start().len();
+        let word_len = rest[word_start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - word_start);
+        self.cursor += word_start + word_len;
+    }
+
+    /// Deletes the character before the cursor.
+    pub fn backspace(&mut self) {
+        let start = self.previous_boundary();
+        self.remove(start..self.cursor);
+    }
+
+    /// Deletes the character under the cursor.
+    pub fn delete(&mut self) {
+        let end = self.next_boundary();
+        self.remove(self.cursor..end);
+    }
+
+    /// Deletes the word before the cursor, like Ctrl+W in a shell.
+    pub fn delete_word_before(&mut self) {
+        let start = self.previous_word_start();
+        self.remove(start..self.cursor);
+    }
+
+    /// Deletes everything before the cursor on its line.
+    pub fn delete_to_start(&mut self) {
+        self.remove(self.line_start()..self.cursor);
+    }
+
+    /// Deletes everything from the cursor to the end of its line.
+    pub fn delete_to_end(&mut self) {
+        self.remove(self.cursor..self.line_end());
+    }
+
+    /// Applies an editing key. Returns false if the key is not an editing key,
+    /// so the caller can handle it.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('w') if ctrl => self.delete_word_before(),
+            KeyCode::Char('u') if ctrl => self.delete_to_start(),
+            KeyCode::Char('k') if ctrl => self.delete_to_end(),
+            KeyCode::Char('a') if ctrl => self.move_home(),
+            KeyCode::Char('e') if ctrl => self.move_end(),
+            KeyCode::Char(_) if ctrl => return false,
+            KeyCode::Char(c) => self.insert(c),
+            KeyCode::Backspace if ctrl || alt => self.delete_word_before(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left if ctrl || alt => self.move_word_left(),
+            KeyCode::Right if ctrl || alt => self.move_word_right(),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Up if self.multiline => self.move_up(),
+            KeyCode::Down if self.multiline => self.move_down(),
+            KeyCode::Home => self.move_home(),
+            KeyCode::End => self.move_end(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.value[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.value[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |grapheme| self.cursor + grapheme.len())
+    }
+
+    fn line_start(&self) -> usize {
+        self.value[..self.cursor]
+            .rfind('\n')
+            .map_or(0, |index| index + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.value[self.cursor..]
+            .find('\n')
+            .map_or(self.value.len(), |index| self.cursor + index)
+    }
+
+    fn move_right_within_line(&mut self, graphemes: usize) {
+        let line_end = self.line_end();
+        let offset: usize = self.value[self.cursor..line_end]
+            .graphemes(true)
+            .take(graphemes)
+            .map(str::len)
+            .sum();
+        self.cursor += offset;
+    }
+
+    fn previous_word_start(&self) -> usize {
+        let before = self.value[..self.cursor].trim_end();
+        before.rfind(char::is_whitespace).map_or(0, |index| {
+            index + before[index..].chars().next().map_or(0, char::len_utf8)
+        })
+    }
+
+    /// Makes room for `additional` bytes. Secret text is copied to the new
+    /// buffer by hand so the old one can be wiped.
+    fn reserve(&mut self, additional: usize) {
+        if !self.secret || self.value.capacity() - self.value.len() >= additional {
+            return;
+        }
+        let capacity = (self.value.len() + additional).max(self.value.capacity() * 2);
+        let mut value = String::with_capacity(capacity);
+        value.push_str(&self.value);
+        std::mem::replace(&mut self.value, value).zeroize();
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        let start = range.start;
+        if self.secret {
+            // Shifting the tail in place would leave its old copy behind the end of the string.
+            let mut value = String::with_capacity(self.value.capacity());
+            value.push_str(&self.value[..range.start]);
+            value.push_str(&self.value[range.end..]);
+            std::mem::replace(&mut self.value, value).zeroize();
+        } else {
+            self.value.replace_range(range, "");
+        }
+        self.cursor = start;
+    }
+}
+
+impl Zeroize for TextInput {
+    fn zeroize(&mut self) {
+        self.value.zeroize();
+        self.cursor = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(value: &str) -> TextInput {
+        let mut input = TextInput::new();
+        input.set(value);
+        input
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn cursor_moves_over_whole_graphemes() {
+        // "é" as e + combining acute, a flag made of two code points, and a wide character.
+        let mut input = input("ae\u{301}🇫🇷語");
+        input.move_left();
+        assert_eq!(input.cursor_column(), 4);
+        input.move_left();
+        assert_eq!(input.cursor_column(), 2);
+        input.move_left();
+        assert_eq!(input.cursor_column(), 1);
+        input.backspace();
+        assert_eq!(input.value(), "e\u{301}🇫🇷語");
+        input.delete();
+        assert_eq!(input.value(), "🇫🇷語");
+        input.move_right();
+        input.insert('x');
+        assert_eq!(input.value(), "🇫🇷x語");
+        input.move_end();
+        input.move_right();
+        assert_eq!(input.cursor_column(), 5);
+    }
+
+    #[test]
+    fn deletes_words_before_the_cursor() {
+        let mut input = input("ssh -i ~/.ssh/id_é  ");
+        assert!(input.handle_key(ctrl('w')));
+        assert_eq!(input.value(), "ssh -i ");
+        input.move_word_left();
+        input.delete_word_before();
+        assert_eq!(input.value(), "-i ");
+        assert_eq!(input.cursor_column(), 0);
+        input.delete_word_before();
+        assert_eq!(input.value(), "-i ");
+
+        input.move_end();
+        assert!(input.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT)));
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn moves_and_deletes_by_line() {
+        let mut input = input("first\nsecond");
+        assert!(input.handle_key(ctrl('a')));
+        input.move_right();
+        assert!(input.handle_key(ctrl('k')));
+        assert_eq!(input.value(), "first\ns");
+        assert!(input.handle_key(ctrl('u')));
+        assert_eq!(input.value(), "first\n");
+        assert_eq!(input.cursor_line(), 1);
+    }
+
+    #[test]
+    fn pasted_text_stays_on_one_line() {
+        let mut input = input("a");
+        input.insert_str("b\tc\r\nd\u{7}e\n");
+        assert_eq!(input.value(), "ab c de");
+        assert_eq!(input.cursor_column(), 7);
+        assert!(!input.handle_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn multiline_input_keeps_pasted_lines() {
+        let mut input = TextInput::multiline();
+        input.insert_str("one\r\ntwo\rthree\n\n");
+        assert_eq!(input.value(), "one\ntwo\nthree");
+        input.insert_newline();
+        input.insert_str("x");
+        assert_eq!(input.value(), "one\ntwo\nthree\nx");
+        assert_eq!(input.line_scroll_offset(2), 2);
+
+        let mut single = TextInput::new();
+        single.insert_newline();
+        assert!(single.is_empty());
+    }
+
+    #[test]
+    fn multiline_input_moves_between_lines_by_grapheme() {
+        let mut input = TextInput::multiline();
+        input.set("long line\n語\nabc");
+        input.move_up();
+        assert_eq!((input.cursor_line(), input.cursor_column()), (1, 2));
+        input.move_up();
+        assert_eq!((input.cursor_line(), input.cursor_column()), (0, 1));
+        input.move_up();
+        assert_eq!((input.cursor_line(), input.cursor_column()), (0, 1));
+        input.move_end();
+        input.move_down();
+        assert_eq!((input.cursor_line(), input.cursor_column()), (1, 2));
+        assert!(input.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)));
+        assert_eq!((input.cursor_line(), input.cursor_column()), (2, 1));
+        input.move_down();
+        assert_eq!((input.cursor_line(), input.cursor_column()), (2, 1));
+    }
+
+    #[test]
+    fn secret_input_is_masked_per_grapheme() {
+        let mut input = TextInput::secret();
+        input.insert_str("pä\u{301}ss語");
+        assert_eq!(input.display_text(), "*****");
+        input.move_left();
+        assert_eq!(input.cursor_column(), 4);
+        assert_eq!(input.scroll_offset(3), 2);
+
+        input.clear();
+        assert!(input.is_empty());
+        assert_eq!(input.display_text(), "");
+    }
+}
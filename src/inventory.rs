@@ -0,0 +1,167 @@
+use ssh_key::{PrivateKey, PublicKey};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use zeroize::Zeroizing;
+
+use crate::authorized_keys::AuthorizedKeysFile;
+use crate::backend::Filesystem;
+use crate::keys::{self, SecurityKeyInfo};
+use crate::known_hosts::KnownHostsFile;
+use crate::ssh_files::{self, FileKind, PrivateKeyInfo};
+
+/// What is known about a file in `~/.ssh`, parsed once per modification.
+#[derive(Clone, Debug)]
+pub struct KeyFile {
+    pub kind: FileKind,
+    /// `None` for private keys, which are not kept in memory, and for files
+    /// that could not be read.
+    pub content: Option<String>,
+    /// The key of a public key file, or the one stored next to the secret of
+    /// a private key.
+    pub public_key: Option<PublicKey>,
+    pub private_key: Option<PrivateKeyInfo>,
+    /// Security key details; flags only come from unencrypted private keys.
+    pub security_key: Option<SecurityKeyInfo>,
+    pub known_hosts: Option<KnownHostsFile>,
+    pub authorized_keys: Option<AuthorizedKeysFile>,
+    /// Content of a private key while it is revealed, wiped when hidden.
+    pub revealed: Option<Zeroizing<String>>,
+    /// `None` once invalidated, so the next refresh reads the file again.
+    modified: Option<SystemTime>,
+}
+
+impl KeyFile {
+    fn read(fs: &dyn Filesystem, path: &Path, modified: SystemTime) -> Self {
+        let mut file = KeyFile {
+            kind: FileKind::Unknown,
+            content: None,
+            public_key: None,
+            private_key: None,
+            security_key: None,
+            known_hosts: None,
+            authorized_keys: None,
+            revealed: None,
+            modified: Some(modified),
+        };
+        let Ok(content) = fs.read_to_string(path) else {
+            return file;
+        };
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        file.kind = ssh_files::classify(file_name, &content);
+        match file.kind {
+            FileKind::PrivateKey => {
+                let info = ssh_files::inspect_private_key(&content);
+                file.security_key = info.public_key.as_ref().and_then(|public_key| {
+                    let private_key = PrivateKey::from_openssh(&content).ok();
+                    keys::security_key_info(public_key, private_key.as_ref())
+                });
+                file.public_key = info.public_key.clone();
+                file.private_key = Some(info);
+            }
+            FileKind::PublicKey => {
+                file.public_key = PublicKey::from_openssh(content.trim()).ok();
+                file.security_key = file
+                    .public_key
+                    .as_ref()
+                    .and_then(|public_key| keys::security_key_info(public_key, None));
+                file.content = Some(content);
+            }
+            FileKind::KnownHosts => {
+                file.known_hosts = Some(KnownHostsFile::parse(path, &content));
+                file.content = Some(content);
+            }
+            FileKind::AuthorizedKeys => {
+                file.authorized_keys = Some(AuthorizedKeysFile::parse(path, &content));
+                file.content = Some(content);
+            }
+            _ => file.content = Some(content),
+        }
+        file
+    }
+
+    fn reveal(&mut self, fs: &dyn Filesystem, path: &Path) {
+        self.revealed = fs.read_to_string(path).ok().map(Zeroizing::new);
+    }
+}
+
+/// Parsed files of `~/.ssh`, read again only when their modification time
+/// changes or they are invalidated.
+#[derive(Debug, Default)]
+pub struct KeyInventory {
+    files: HashMap<PathBuf, KeyFile>,
+}
+
+impl KeyInventory {
+    /// Reads the files in `paths` that are new or have changed since they were
+    /// read, and forgets the others, including those in `paths` that do not
+    /// exist.
+    pub fn refresh(&mut self, fs: &dyn Filesystem, paths: impl IntoIterator<Item = PathBuf>) {
+        let mut files = HashMap::new();
+        for path in paths {
+            let Ok(modified) = fs.modified(&path) else {
+                continue;
+            };
+            let file = match self.files.remove(&path) {
+                Some(file) if file.modified == Some(modified) => file,
+                old => {
+                    let mut file = KeyFile::read(fs, &path, modified);
+                    // A revealed key stays revealed, with its new content.
+                    if old.is_some_and(|old| old.revealed.is_some()) {
+                        file.reveal(fs, &path);
+                    }
+                    file
+                }
+            };
+            files.insert(path, file);
+        }
+        self.files = files;
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&KeyFile> {
+        self.files.get(path)
+    }
+
+    /// Makes the next refresh read `path` again, for changes made within the
+    /// resolution of the modification time.
+    pub fn invalidate(&mut self, path: &Path) {
+        if let Some(file) = self.files.get_mut(path) {
+            file.modified = None;
+        }
+    }
+
+    /// Reads the content of the private key at `path`, which must have been
+    /// refreshed, to show it until [`KeyInventory::hide`].
+    pub fn reveal(&mut self, fs: &dyn Filesystem, path: &Path) -> bool {
+        match self.files.get_mut(path) {
+            Some(file) if file.kind == FileKind::PrivateKey => {
+                file.reveal(fs, path);
+                file.revealed.is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops the revealed content of `path`, wiping it from memory.
+    pub fn hide(&mut self, path: &Path) {
+        if let Some(file) = self.files.get_mut(path) {
+            file.revealed = None;
+        }
+    }
+
+    /// Names of the public key files, by public key blob.
+    pub fn public_key_files(&self) -> HashMap<Vec<u8>, String> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.kind == FileKind::PublicKey)
+            .filter_map(|(path, file)| {
+                let blob = file.public_key.as_ref()?.to_bytes().ok()?;
+                let file_name = path.file_name()?.to_str()?;
+                Some((blob, file_name.to_string()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,166 @@
+use ssh_key::PublicKey;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::agent::{Constraints, Identity};
+use crate::event::TerminalEvent;
+
+/// Frames of the spinner shown in the footer while a job runs.
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Identifies a job, so its result can be told apart from a stale one.
+pub type JobId = u64;
+
+/// A finished job, delivered to the UI thread as [`TerminalEvent::Job`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobResult {
+    pub id: JobId,
+    pub output: JobOutput,
+}
+
+/// What a job produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobOutput {
+    /// A key pair was generated, or failed to be.
+    KeyCreated {
+        key_path: PathBuf,
+        /// Commands run on the way, for the command log.
+        log: Vec<String>,
+        result: Result<(), String>,
+    },
+    /// A private key was offered to the agent.
+    AgentAdd {
+        path: PathBuf,
+        constraints: Constraints,
+        outcome: AgentAddOutcome,
+    },
+    /// An identity was removed from the agent. `Ok(false)` if it was not in
+    /// the agent.
+    AgentRemove {
+        /// The key file or identity, for the command log.
+        label: String,
+        result: Result<bool, String>,
+    },
+    /// All identities were removed from the agent.
+    AgentRemoveAll(Result<(), String>),
+    /// The agent was locked or unlocked.
+    AgentLock(AgentLockOutcome),
+    /// The identities held by the agent, or why they could not be listed.
+    AgentIdentities(Result<Vec<Identity>, String>),
+    /// A private key was re-encrypted, or failed to be.
+    PassphraseChanged {
+        path: PathBuf,
+        outcome: PassphraseOutcome,
+    },
+}
+
+/// How adding a key to the agent ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentAddOutcome {
+    Added(PublicKey),
+    AlreadyAdded,
+    /// The key is encrypted and no passphrase was given.
+    NeedsPassphrase,
+    IncorrectPassphrase,
+    /// Carries the message for the command log.
+    Failed(String),
+}
+
+/// How locking or unlocking the agent ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentLockOutcome {
+    Locked,
+    AlreadyLocked,
+    Unlocked,
+    NotLocked,
+    IncorrectPassword,
+    /// Carries the message for the command log.
+    Failed(String),
+}
+
+/// How changing the passphrase of a private key ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PassphraseOutcome {
+    Changed,
+    Removed,
+    IncorrectPassphrase,
+    Failed(String),
+}
+
+struct RunningJob {
+    id: JobId,
+    name: String,
+}
+
+/// Runs blocking work on worker threads and reports back through the event
+/// channel, so the UI keeps drawing.
+pub struct Jobs {
+    sender: mpsc::Sender<TerminalEvent>,
+    running: Vec<RunningJob>,
+    next_id: JobId,
+    spinner_frame: usize,
+}
+
+impl Jobs {
+    pub fn new(sender: mpsc::Sender<TerminalEvent>) -> Self {
+        Self {
+            sender,
+            running: Vec::new(),
+            next_id: 0,
+            spinner_frame: 0,
+        }
+    }
+
+    /// Starts `job` on a new thread. `name` is shown in the footer until its
+    /// result has been handed to [`Jobs::finish`].
+    pub fn spawn<F>(&mut self, name: impl Into<String>, job: F) -> JobId
+    where
+        F: FnOnce() -> JobOutput + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push(RunningJob {
+            id,
+            name: name.into(),
+        });
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let output = job();
+            // The receiver is gone only when the app is shutting down.
+            let _ = sender.send(TerminalEvent::Job(JobResult { id, output }));
+        });
+        id
+    }
+
+    /// Forgets a job once its result has arrived.
+    pub fn finish(&mut self, id: JobId) {
+        self.running.retain(|job| job.id != id);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Returns whether a job with this name has not finished yet.
+    pub fn is_running(&self, name: &str) -> bool {
+        self.running.iter().any(|job| job.name == name)
+    }
+
+    /// Advances the spinner by one frame.
+    pub fn tick(&mut self) {
+        self.spinner_frame = (self.spinner_frame + 1) % SPINNER_FRAMES.len();
+    }
+
+    /// The spinner and the name of the oldest running job, e.g.
+    /// `⠋ Creating SSH key (+1 more)`.
+    pub fn status(&self) -> Option<String> {
+        let first = self.running.first()?;
+        let mut status = format!("{} {}", SPINNER_FRAMES[self.spinner_frame], first.name);
+        if self.running.len() > 1 {
+            status.push_str(&format!(" (+{} more)", self.running.len() - 1));
+        }
+        Some(status)
+    }
+}
@@ -0,0 +1,509 @@
+use md5::{Digest, Md5};
+use ssh_key::private::{KeypairData, RsaKeypair};
+use ssh_key::public::KeyData;
+use ssh_key::rand_core::OsRng;
+use ssh_key::{Algorithm, EcdsaCurve, HashAlg, LineEnding, PrivateKey, PublicKey};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::backend::{self, Filesystem};
+
+/// Flags of a FIDO2 key, from OpenSSH's `sk-api.h`.
+const SK_USER_PRESENCE_REQD: u8 = 0x01;
+const SK_USER_VERIFICATION_REQD: u8 = 0x04;
+const SK_RESIDENT_KEY: u8 = 0x20;
+
+/// Details of a FIDO2 security key pair (`ed25519-sk` or `ecdsa-sk`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityKeyInfo {
+    /// Application string, `ssh:` unless set at creation.
+    pub application: String,
+    /// Key flags; only known when the private key file is not encrypted.
+    pub flags: Option<u8>,
+}
+
+impl SecurityKeyInfo {
+    /// Flags as ssh-keygen `-O` option names.
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        let Some(flags) = self.flags else {
+            return Vec::new();
+        };
+        let mut names = Vec::new();
+        if flags & SK_RESIDENT_KEY != 0 {
+            names.push("resident");
+        }
+        if flags & SK_USER_VERIFICATION_REQD != 0 {
+            names.push("verify-required");
+        }
+        if flags & SK_USER_PRESENCE_REQD == 0 {
+            names.push("no-touch-required");
+        }
+        names
+    }
+
+    /// Badge for the file list, e.g. `[SK ssh: resident]`.
+    pub fn badge(&self) -> String {
+        let mut badge = format!("[SK {}", self.application);
+        for name in self.flag_names() {
+            badge.push(' ');
+            badge.push_str(name);
+        }
+        badge.push(']');
+        badge
+    }
+}
+
+/// Hash used to show key fingerprints, like `ssh-keygen -E`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FingerprintHash {
+    #[default]
+    Sha256,
+    /// Still shown by some cloud consoles, e.g. for AWS key pairs.
+    Md5,
+}
+
+impl FingerprintHash {
+    /// Name as printed by ssh-keygen.
+    pub fn label(self) -> &'static str {
+        match self {
+            FingerprintHash::Sha256 => "SHA256",
+            FingerprintHash::Md5 => "MD5",
+        }
+    }
+
+    /// The other hash.
+    pub fn toggle(self) -> Self {
+        match self {
+            FingerprintHash::Sha256 => FingerprintHash::Md5,
+            FingerprintHash::Md5 => FingerprintHash::Sha256,
+        }
+    }
+}
+
+/// Errors returned by the in-process key generator.
+#[derive(Debug)]
+pub enum KeygenError {
+    /// The key type has to be generated by `ssh-keygen`.
+    Unsupported(String),
+    /// The key size is not valid for the key type.
+    InvalidBits(String, u32),
+    /// Generating, encrypting or writing the key failed.
+    Key(ssh_key::Error),
+}
+
+impl fmt::Display for KeygenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeygenError::Unsupported(key_type) => {
+                write!(f, "{} keys cannot be generated natively", key_type)
+            }
+            KeygenError::InvalidBits(key_type, bits) => {
+                write!(f, "Invalid {} key length: {} bits", key_type, bits)
+            }
+            KeygenError::Key(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for KeygenError {}
+
+impl From<ssh_key::Error> for KeygenError {
+    fn from(err: ssh_key::Error) -> Self {
+        KeygenError::Key(err)
+    }
+}
+
+/// Short key type label, as printed by `ssh-keygen -l`.
+pub fn key_type_label(algorithm: &Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Dsa => "DSA",
+        Algorithm::Ecdsa { .. } => "ECDSA",
+        Algorithm::Ed25519 => "ED25519",
+        Algorithm::Rsa { .. } => "RSA",
+        Algorithm::SkEcdsaSha2NistP256 => "ECDSA-SK",
+        Algorithm::SkEd25519 => "ED25519-SK",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Key size in bits, if it can be derived from the public key.
+pub fn key_bits(key_data: &KeyData) -> Option<u32> {
+    match key_data {
+        KeyData::Dsa(key) => mpint_bits(key.p.as_positive_bytes()?),
+        KeyData::Ecdsa(key) => Some(match key.curve() {
+            EcdsaCurve::NistP256 => 256,
+            EcdsaCurve::NistP384 => 384,
+            EcdsaCurve::NistP521 => 521,
+        }),
+        KeyData::Ed25519(_) => Some(256),
+        KeyData::Rsa(key) => mpint_bits(key.n.as_positive_bytes()?),
+        KeyData::SkEcdsaSha2NistP256(_) => Some(256),
+        KeyData::SkEd25519(_) => Some(256),
+        _ => None,
+    }
+}
+
+fn mpint_bits(bytes: &[u8]) -> Option<u32> {
+    let first = bytes.first()?;
+    Some(bytes.len() as u32 * 8 - first.leading_zeros())
+}
+
+/// SHA256 fingerprint in the `SHA256:...` form.
+pub fn sha256_fingerprint(public_key: &PublicKey) -> String {
+    public_key.fingerprint(HashAlg::Sha256).to_string()
+}
+
+/// MD5 fingerprint in the `MD5:xx:xx:...` form older tools still print.
+pub fn md5_fingerprint(public_key: &PublicKey) -> String {
+    let hex: Vec<String> = fingerprint_digest(public_key, FingerprintHash::Md5)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("MD5:{}", hex.join(":"))
+}
+
+/// Fingerprint of `public_key` using `hash`, like `ssh-keygen -l -E <hash>`.
+pub fn fingerprint(public_key: &PublicKey, hash: FingerprintHash) -> String {
+    match hash {
+        FingerprintHash::Sha256 => sha256_fingerprint(public_key),
+        FingerprintHash::Md5 => md5_fingerprint(public_key),
+    }
+}
+
+fn fingerprint_digest(public_key: &PublicKey, hash: FingerprintHash) -> Vec<u8> {
+    match hash {
+        FingerprintHash::Sha256 => public_key.fingerprint(HashAlg::Sha256).as_bytes().to_vec(),
+        FingerprintHash::Md5 => {
+            let blob = public_key.to_bytes().unwrap_or_default();
+            Md5::digest(blob).to_vec()
+        }
+    }
+}
+
+/// The "randomart" image of a fingerprint, as printed by `ssh-keygen -lv`.
+pub fn randomart(public_key: &PublicKey, hash: FingerprintHash) -> String {
+    let title = match key_bits(public_key.key_data()) {
+        Some(bits) => format!("[{} {}]", key_type_label(&public_key.algorithm()), bits),
+        None => format!("[{}]", key_type_label(&public_key.algorithm())),
+    };
+    drunken_bishop(
+        &fingerprint_digest(public_key, hash),
+        &title,
+        &format!("[{}]", hash.label()),
+    )
+}
+
+/// OpenSSH's "drunken bishop" walk, see `fingerprint_randomart` in sshkey.c.
+fn drunken_bishop(digest: &[u8], title: &str, footer: &str) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+    let start = SYMBOLS.len() - 2;
+    let end = SYMBOLS.len() - 1;
+
+    let mut field = [[0usize; HEIGHT]; WIDTH];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+    for byte in digest {
+        let mut input = *byte;
+        for _ in 0..4 {
+            x = if input & 1 != 0 {
+                (x + 1).min(WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if input & 2 != 0 {
+                (y + 1).min(HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            if field[x][y] < start - 1 {
+                field[x][y] += 1;
+            }
+            input >>= 2;
+        }
+    }
+    field[WIDTH / 2][HEIGHT / 2] = start;
+    field[x][y] = end;
+
+    let border = |label: &str| {
+        let left = WIDTH.saturating_sub(label.len()) / 2;
+        let right = WIDTH.saturating_sub(left + label.len());
+        format!("+{}{}{}+", "-".repeat(left), label, "-".repeat(right))
+    };
+    let mut lines = vec![border(title)];
+    for y in 0..HEIGHT {
+        let row: String = (0..WIDTH)
+            .map(|x| SYMBOLS[field[x][y].min(end)] as char)
+            .collect();
+        lines.push(format!("|{}|", row));
+    }
+    lines.push(border(footer));
+    lines.join("\n")
+}
+
+/// Returns whether the key type lives on a FIDO2 security key.
+pub fn is_security_key_type(key_type: &str) -> bool {
+    key_type.ends_with("-sk")
+}
+
+/// Security key details of a key pair, or `None` for other key types. The
+/// application comes from the public key, the flags from `private_key` if it
+/// is given and not encrypted.
+pub fn security_key_info(
+    public_key: &PublicKey,
+    private_key: Option<&PrivateKey>,
+) -> Option<SecurityKeyInfo> {
+    let application = match public_key.key_data() {
+        KeyData::SkEd25519(key) => key.application(),
+        KeyData::SkEcdsaSha2NistP256(key) => key.application(),
+        _ => return None,
+    };
+    let flags = private_key
+        .filter(|private_key| !private_key.is_encrypted())
+        .and_then(|private_key| match private_key.key_data() {
+            KeypairData::SkEd25519(key) => Some(key.flags()),
+            KeypairData::SkEcdsaSha2NistP256(key) => Some(key.flags()),
+            _ => None,
+        });
+    Some(SecurityKeyInfo {
+        application: application.to_string(),
+        flags,
+    })
+}
+
+/// Key sizes offered for a key type, smallest first. Empty for fixed-size
+/// keys, which includes DSA: ssh-keygen only generates 1024-bit DSA keys.
+pub fn key_bits_options(key_type: &str) -> &'static [&'static str] {
+    match key_type {
+        "rsa" => &["3072", "4096", "8192"],
+        "ecdsa" => &["256", "384", "521"],
+        _ => &[],
+    }
+}
+
+/// Checks a key size against what the generator for `key_type` accepts.
+pub fn validate_key_bits(key_type: &str, bits: Option<u32>) -> Result<(), String> {
+    let valid = match (key_type, bits) {
+        ("dsa" | "ed25519" | "ed25519-sk" | "ecdsa-sk", None) => true,
+        ("rsa", Some(bits)) => (2048..=16384).contains(&bits),
+        ("ecdsa", Some(bits)) => [256, 384, 521].contains(&bits),
+        _ => false,
+    };
+    match (valid, bits) {
+        (true, _) => Ok(()),
+        (false, Some(bits)) => Err(format!("{} bits is not valid for {} keys", bits, key_type)),
+        (false, None) => Err(format!("{} keys need a key size", key_type)),
+    }
+}
+
+/// Re-encrypts a private key with a new passphrase, like `ssh-keygen -p`.
+/// An empty `new_passphrase` removes the encryption. The key is written to a
+/// temporary file first and renamed over the original.
+pub fn change_passphrase(
+    fs: &dyn Filesystem,
+    path: &Path,
+    old_passphrase: &str,
+    new_passphrase: &str,
+) -> Result<(), ssh_key::Error> {
+    let mut private_key = backend::read_private_key(fs, path)?;
+    if private_key.is_encrypted() {
+        private_key = private_key.decrypt(old_passphrase)?;
+    }
+    if !new_passphrase.is_empty() {
+        private_key = private_key.encrypt(&mut OsRng, new_passphrase)?;
+    }
+
+    write_private_key(fs, path, &private_key)
+}
+
+/// Generates a key pair in-process, like `ssh-keygen -t <key_type> -b <bits>`.
+/// Without `bits`, the ssh-keygen default size for the type is used.
+/// The private key is written to `path` with mode 0600, encrypted with
+/// bcrypt-pbkdf and aes256-ctr when a passphrase is given, and the public key
+/// to `path.pub` with mode 0644.
+///
+/// Returns [`KeygenError::Unsupported`] for key types that need `ssh-keygen`.
+pub fn generate_key_pair(
+    fs: &dyn Filesystem,
+    path: &Path,
+    key_type: &str,
+    bits: Option<u32>,
+    passphrase: &str,
+    comment: &str,
+) -> Result<(), KeygenError> {
+    let invalid_bits = |bits| KeygenError::InvalidBits(key_type.to_string(), bits);
+    let mut private_key = match key_type {
+        "ed25519" => PrivateKey::random(&mut OsRng, Algorithm::Ed25519)?,
+        "ecdsa" => {
+            let curve = match bits.unwrap_or(256) {
+                256 => EcdsaCurve::NistP256,
+                384 => EcdsaCurve::NistP384,
+                521 => EcdsaCurve::NistP521,
+                bits => return Err(invalid_bits(bits)),
+            };
+            PrivateKey::random(&mut OsRng, Algorithm::Ecdsa { curve })?
+        }
+        "rsa" => {
+            let bits = bits.unwrap_or(3072);
+            if !(2048..=16384).contains(&bits) {
+                return Err(invalid_bits(bits));
+            }
+            let keypair = RsaKeypair::random(&mut OsRng, bits as usize)?;
+            PrivateKey::new(KeypairData::from(keypair), "")?
+        }
+        _ => return Err(KeygenError::Unsupported(key_type.to_string())),
+    };
+    private_key.set_comment(comment);
+    // Encrypting moves the comment into the encrypted part, so take the
+    // public key line first.
+    let mut public_key = private_key.public_key().to_openssh()?;
+    public_key.push('\n');
+    if !passphrase.is_empty() {
+        private_key = private_key.encrypt(&mut OsRng, passphrase)?;
+    }
+
+    let public_key_path = public_key_path(path);
+    fs.create_new(&public_key_path, public_key.as_bytes(), 0o644)
+        .map_err(ssh_key::Error::from)?;
+    if let Err(err) = write_private_key(fs, path, &private_key) {
+        let _ = fs.remove_file(&public_key_path);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Path of the `.pub` file next to a private key.
+pub fn public_key_path(path: &Path) -> PathBuf {
+    let mut public_key_path = path.as_os_str().to_owned();
+    public_key_path.push(".pub");
+    PathBuf::from(public_key_path)
+}
+
+/// Writes a private key with mode 0600 through a temporary file, so an
+/// existing key is never left half-written.
+fn write_private_key(
+    fs: &dyn Filesystem,
+    path: &Path,
+    private_key: &PrivateKey,
+) -> Result<(), ssh_key::Error> {
+    let content = private_key.to_openssh(LineEnding::LF)?;
+    backend::replace_file(fs, path, content.as_bytes(), 0o600)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+
+    /// Generates a key pair in an empty home and checks it reads back with
+    /// the passphrase and matches its `.pub` file.
+    fn assert_round_trip(key_type: &str, bits: Option<u32>, algorithm: Algorithm) {
+        let backend = FakeBackend::new();
+        let path = backend.ssh_dir().join("id_test");
+        generate_key_pair(&backend, &path, key_type, bits, "secret", "user@host").unwrap();
+
+        assert_eq!(backend.file(&path).unwrap().mode, 0o600);
+        let public_key_path = public_key_path(&path);
+        assert_eq!(backend.file(&public_key_path).unwrap().mode, 0o644);
+        assert_eq!(backend.paths(), [path.clone(), public_key_path.clone()]);
+
+        let private_key = backend::read_private_key(&backend, &path).unwrap();
+        assert!(private_key.is_encrypted());
+        assert!(matches!(
+            private_key.decrypt("wrong"),
+            Err(ssh_key::Error::Crypto)
+        ));
+        let private_key = private_key.decrypt("secret").unwrap();
+        assert_eq!(private_key.algorithm(), algorithm);
+        assert_eq!(private_key.comment(), "user@host");
+
+        let public_key = backend::read_public_key(&backend, &public_key_path).unwrap();
+        assert_eq!(public_key.key_data(), private_key.public_key().key_data());
+        assert_eq!(public_key.comment(), "user@host");
+        assert_eq!(key_bits(public_key.key_data()), bits.or(Some(256)));
+    }
+
+    #[test]
+    fn generates_ed25519_key_pairs() {
+        assert_round_trip("ed25519", None, Algorithm::Ed25519);
+    }
+
+    #[test]
+    fn generates_ecdsa_key_pairs() {
+        assert_round_trip(
+            "ecdsa",
+            Some(384),
+            Algorithm::Ecdsa {
+                curve: EcdsaCurve::NistP384,
+            },
+        );
+    }
+
+    #[test]
+    fn generates_rsa_key_pairs() {
+        assert_round_trip("rsa", Some(2048), Algorithm::Rsa { hash: None });
+    }
+
+    #[test]
+    fn generate_key_pair_rejects_what_it_cannot_make() {
+        let backend = FakeBackend::new();
+        let path = backend.ssh_dir().join("id_test");
+        assert!(matches!(
+            generate_key_pair(&backend, &path, "ecdsa", Some(512), "", ""),
+            Err(KeygenError::InvalidBits(_, 512))
+        ));
+        assert!(matches!(
+            generate_key_pair(&backend, &path, "dsa", None, "", ""),
+            Err(KeygenError::Unsupported(_))
+        ));
+        assert!(backend.paths().is_empty());
+
+        // An existing public key is never overwritten.
+        let backend = backend.with_ssh_file("id_test.pub", "old");
+        assert!(generate_key_pair(&backend, &path, "ed25519", None, "", "").is_err());
+        assert_eq!(backend.ssh_file("id_test.pub").unwrap(), "old");
+        assert!(backend.file(&path).is_none());
+    }
+
+    #[test]
+    fn change_passphrase_round_trips() {
+        let backend = FakeBackend::new();
+        let path = backend.ssh_dir().join("id_test");
+        generate_key_pair(&backend, &path, "ed25519", None, "old", "user@host").unwrap();
+        let public_key = backend::read_public_key(&backend, &public_key_path(&path)).unwrap();
+
+        assert!(matches!(
+            change_passphrase(&backend, &path, "wrong", "new"),
+            Err(ssh_key::Error::Crypto)
+        ));
+        change_passphrase(&backend, &path, "old", "new").unwrap();
+        let private_key = backend::read_private_key(&backend, &path).unwrap();
+        assert!(private_key.decrypt("old").is_err());
+        let private_key = private_key.decrypt("new").unwrap();
+        assert_eq!(private_key.public_key().key_data(), public_key.key_data());
+
+        change_passphrase(&backend, &path, "new", "").unwrap();
+        let private_key = backend::read_private_key(&backend, &path).unwrap();
+        assert!(!private_key.is_encrypted());
+        assert_eq!(private_key.public_key().key_data(), public_key.key_data());
+        assert_eq!(private_key.comment(), "user@host");
+    }
+
+    #[test]
+    fn change_passphrase_replaces_a_stale_temporary_file() {
+        let backend = FakeBackend::new();
+        let path = backend.ssh_dir().join("id_test");
+        let temp_path = backend.ssh_dir().join("id_test.tmp");
+        generate_key_pair(&backend, &path, "ed25519", None, "", "user@host").unwrap();
+        backend.write(&temp_path, b"stale", 0o644).unwrap();
+
+        change_passphrase(&backend, &path, "", "secret").unwrap();
+
+        assert_eq!(backend.file(&path).unwrap().mode, 0o600);
+        assert!(backend.file(&temp_path).is_none());
+        assert!(backend::read_private_key(&backend, &path)
+            .unwrap()
+            .is_encrypted());
+    }
+}
@@ -0,0 +1,434 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use ssh_key::rand_core::{OsRng, RngCore};
+use ssh_key::PublicKey;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::Filesystem;
+use crate::ssh_config::match_pattern_list;
+
+/// Prefix of a hashed host name, `|1|salt|hash`.
+const HASH_MAGIC: &str = "|1|";
+
+/// Marker at the start of a known_hosts line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Marker {
+    CertAuthority,
+    Revoked,
+}
+
+impl Marker {
+    /// Marker as written in the file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Marker::CertAuthority => "@cert-authority",
+            Marker::Revoked => "@revoked",
+        }
+    }
+}
+
+/// The host part of a known_hosts line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPatterns {
+    /// Comma-separated host names or patterns.
+    Plain(Vec<String>),
+    /// A single host name hashed with HMAC-SHA1, as written by `ssh-keygen -H`.
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// A parsed known_hosts line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownHostEntry {
+    /// Index of the line in the file.
+    pub line: usize,
+    pub marker: Option<Marker>,
+    pub hosts: HostPatterns,
+    /// Key type as written, e.g. `ssh-ed25519`.
+    pub key_type: String,
+    /// The decoded key, if the key type is supported.
+    pub public_key: Option<PublicKey>,
+    pub comment: String,
+}
+
+impl KnownHostEntry {
+    /// Host names for display, or a marker for hashed entries.
+    pub fn hosts_label(&self) -> String {
+        match &self.hosts {
+            HostPatterns::Plain(hosts) => hosts.join(","),
+            HostPatterns::Hashed { .. } => "<hashed>".to_string(),
+        }
+    }
+
+    /// Returns whether the entry applies to `host`, which may be given as
+    /// `host` or `[host]:port`.
+    pub fn matches_host(&self, host: &str) -> bool {
+        match &self.hosts {
+            HostPatterns::Plain(patterns) => match_pattern_list(host, patterns),
+            HostPatterns::Hashed { salt, hash } => &hash_host_with_salt(host, salt) == hash,
+        }
+    }
+}
+
+/// A known_hosts file, keeping unparsed lines so it can be rewritten losslessly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownHostsFile {
+    path: PathBuf,
+    lines: Vec<String>,
+    pub entries: Vec<KnownHostEntry>,
+}
+
+impl KnownHostsFile {
+    /// Reads `~/.ssh/known_hosts`.
+    pub fn load_user_file(fs: &dyn Filesystem) -> io::Result<Self> {
+        Self::load(fs, &fs.ssh_dir().join("known_hosts"))
+    }
+
+    /// Reads a known_hosts file. A missing file yields an empty list.
+    pub fn load(fs: &dyn Filesystem, path: &Path) -> io::Result<Self> {
+        let content = match fs.read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self::parse(path, &content))
+    }
+
+    /// Parses known_hosts content.
+    pub fn parse(path: &Path, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let entries = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| parse_line(index, line))
+            .collect();
+        Self {
+            path: path.to_path_buf(),
+            lines,
+            entries,
+        }
+    }
+
+    /// Path the file is saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries that apply to `host`.
+    pub fn find_host(&self, host: &str) -> Vec<&KnownHostEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.matches_host(host))
+            .collect()
+    }
+
+    /// Removes the line of a single entry.
+    pub fn remove_entry(&mut self, line: usize) {
+        self.remove_lines(&[line]);
+    }
+
+    /// Removes every entry for `host`, like `ssh-keygen -R`. Returns the
+    /// number of entries removed. `@cert-authority` and `@revoked` lines are
+    /// kept, as ssh-keygen does.
+    pub fn remove_host(&mut self, host: &str) -> usize {
+        let lines: Vec<usize> = self
+            .find_host(host)
+            .iter()
+            .filter(|entry| entry.marker.is_none())
+            .map(|entry| entry.line)
+            .collect();
+        self.remove_lines(&lines);
+        lines.len()
+    }
+
+    /// Replaces every plain host name with its hash, like `ssh-keygen -H`.
+    /// Lines listing several hosts are split into one line per host.
+    /// Wildcard and negated patterns cannot be hashed and are left alone, so
+    /// are lines with a marker, as ssh-keygen does. Returns the number of
+    /// host names hashed.
+    pub fn hash_all(&mut self) -> usize {
+        let mut hashed = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+        for (index, line) in self.lines.iter().enumerate() {
+            let entry = self.entries.iter().find(|entry| entry.line == index);
+            let Some(KnownHostEntry {
+                marker: None,
+                hosts: HostPatterns::Plain(hosts),
+                ..
+            }) = entry
+            else {
+                lines.push(line.clone());
+                continue;
+            };
+            if hosts.iter().any(|host| host.contains(['*', '?', '!'])) {
+                lines.push(line.clone());
+                continue;
+            }
+
+            let rest = line
+                .split_whitespace()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join(" ");
+            for host in hosts {
+                lines.push(format!("{} {}", hash_host(host), rest));
+                hashed += 1;
+            }
+        }
+        *self = Self::parse(&self.path, &(lines.join("\n") + "\n"));
+        hashed
+    }
+
+    /// Writes the file, keeping the previous version as `known_hosts.old`
+    /// like ssh-keygen does. Returns the backup path, if a backup was made.
+    pub fn save(&self, fs: &dyn Filesystem) -> io::Result<Option<PathBuf>> {
+        let backup = if fs.exists(&self.path) {
+            let mut backup = self.path.as_os_str().to_owned();
+            backup.push(".old");
+            let backup = PathBuf::from(backup);
+            fs.copy(&self.path, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        let mut content = self.lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        fs.write(&self.path, content.as_bytes(), 0o644)?;
+        Ok(backup)
+    }
+
+    fn remove_lines(&mut self, lines: &[usize]) {
+        let content: Vec<&str> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !lines.contains(index))
+            .map(|(_, line)| line.as_str())
+            .collect();
+        let mut content = content.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        *self = Self::parse(&self.path, &content);
+    }
+}
+
+fn parse_line(index: usize, line: &str) -> Option<KnownHostEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut tokens = line.split_whitespace();
+    let mut first = tokens.next()?;
+    let marker = match first {
+        "@cert-authority" => Some(Marker::CertAuthority),
+        "@revoked" => Some(Marker::Revoked),
+        _ => None,
+    };
+    if marker.is_some() {
+        first = tokens.next()?;
+    }
+
+    let key_type = tokens.next()?.to_string();
+    let key_data = tokens.next()?;
+    let comment = tokens.collect::<Vec<_>>().join(" ");
+    let public_key = PublicKey::from_openssh(&format!("{} {}", key_type, key_data)).ok();
+
+    Some(KnownHostEntry {
+        line: index,
+        marker,
+        hosts: parse_hosts(first),
+        key_type,
+        public_key,
+        comment,
+    })
+}
+
+fn parse_hosts(hosts: &str) -> HostPatterns {
+    if let Some(hashed) = hosts.strip_prefix(HASH_MAGIC) {
+        if let Some((salt, hash)) = hashed.split_once('|') {
+            if let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) {
+                return HostPatterns::Hashed { salt, hash };
+            }
+        }
+    }
+    HostPatterns::Plain(hosts.split(',').map(str::to_string).collect())
+}
+
+fn hash_host_with_salt(host: &str, salt: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(salt).expect("HMAC accepts any key length");
+    mac.update(host.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Hashes a host name with a fresh salt into the `|1|salt|hash` form.
+pub fn hash_host(host: &str) -> String {
+    let mut salt = [0u8; 20];
+    OsRng.fill_bytes(&mut salt);
+    let hash = hash_host_with_salt(host, &salt);
+    format!(
+        "{}{}|{}",
+        HASH_MAGIC,
+        STANDARD.encode(salt),
+        STANDARD.encode(hash)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    /// `example.com` and `[example.com]:2222` hashed with the salt 0, 1, ..., 19.
+    const HASHED_EXAMPLE: &str = "|1|AAECAwQFBgcICQoLDA0ODxAREhM=|nnUK16ANsXd3hL31YfAkGOluSjU=";
+    const HASHED_EXAMPLE_PORT: &str =
+        "|1|AAECAwQFBgcICQoLDA0ODxAREhM=|Wgcx+Fm+LmaWwC7rQ80eIf2uHe0=";
+
+    fn parse(content: &str) -> KnownHostsFile {
+        KnownHostsFile::parse(Path::new("/home/user/.ssh/known_hosts"), content)
+    }
+
+    fn lines(file: &KnownHostsFile) -> Vec<&str> {
+        file.lines.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn parses_markers_and_skips_comments() {
+        let file = parse(&format!(
+            "# managed by hand\n\
+             @cert-authority *.example.com {KEY} ca\n\
+             @revoked example.com {KEY}\n\
+             example.com,10.0.0.1 {KEY} laptop key\n\
+             \n\
+             incomplete\n"
+        ));
+
+        assert_eq!(file.entries.len(), 3);
+        let ca = &file.entries[0];
+        assert_eq!(ca.line, 1);
+        assert_eq!(ca.marker, Some(Marker::CertAuthority));
+        assert_eq!(
+            ca.hosts,
+            HostPatterns::Plain(vec!["*.example.com".to_string()])
+        );
+        assert_eq!(ca.comment, "ca");
+        assert!(ca.public_key.is_some());
+        assert_eq!(file.entries[1].marker, Some(Marker::Revoked));
+
+        let plain = &file.entries[2];
+        assert_eq!(plain.marker, None);
+        assert_eq!(plain.hosts_label(), "example.com,10.0.0.1");
+        assert_eq!(plain.key_type, "ssh-ed25519");
+        assert_eq!(plain.comment, "laptop key");
+
+        assert_eq!(file.find_host("example.com").len(), 2);
+        assert_eq!(file.find_host("10.0.0.1").len(), 1);
+        assert_eq!(file.find_host("www.example.com").len(), 1);
+    }
+
+    #[test]
+    fn matches_hashed_hosts() {
+        let file = parse(&format!(
+            "{HASHED_EXAMPLE} {KEY}\n{HASHED_EXAMPLE_PORT} {KEY}\n"
+        ));
+        let (default_port, port) = (&file.entries[0], &file.entries[1]);
+
+        assert_eq!(default_port.hosts_label(), "<hashed>");
+        assert!(default_port.matches_host("example.com"));
+        assert!(!default_port.matches_host("example.org"));
+        assert!(!default_port.matches_host("[example.com]:2222"));
+        assert!(port.matches_host("[example.com]:2222"));
+        assert!(!port.matches_host("example.com"));
+    }
+
+    #[test]
+    fn matches_hosts_with_a_port() {
+        let file = parse(&format!(
+            "[example.com]:2222 {KEY}\nexample.com {KEY}\n[*.example.org]:2200 {KEY}\n"
+        ));
+
+        let lines = |host| -> Vec<usize> {
+            file.find_host(host)
+                .iter()
+                .map(|entry| entry.line)
+                .collect()
+        };
+        assert_eq!(lines("[example.com]:2222"), [0]);
+        assert_eq!(lines("example.com"), [1]);
+        assert_eq!(lines("[www.example.org]:2200"), [2]);
+        assert_eq!(lines("[www.example.org]:22"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn remove_host_keeps_marker_lines() {
+        let backend = FakeBackend::new().with_ssh_file(
+            "known_hosts",
+            format!(
+                "@cert-authority example.com {KEY}\n\
+                 @revoked example.com {KEY}\n\
+                 example.com {KEY}\n\
+                 {HASHED_EXAMPLE} {KEY}\n\
+                 other.com {KEY}\n"
+            ),
+        );
+        let mut file = KnownHostsFile::load_user_file(&backend).unwrap();
+
+        assert_eq!(file.remove_host("example.com"), 2);
+        assert_eq!(
+            lines(&file),
+            [
+                format!("@cert-authority example.com {KEY}"),
+                format!("@revoked example.com {KEY}"),
+                format!("other.com {KEY}"),
+            ]
+        );
+        assert_eq!(file.remove_host("example.com"), 0);
+
+        let backup = file.save(&backend).unwrap();
+        assert_eq!(
+            backup,
+            Some(PathBuf::from("/home/user/.ssh/known_hosts.old"))
+        );
+        assert_eq!(KnownHostsFile::load_user_file(&backend).unwrap(), file);
+    }
+
+    #[test]
+    fn hash_all_hashes_plain_hosts_only() {
+        let mut file = parse(&format!(
+            "# comment\n\
+             example.com,10.0.0.1 {KEY} laptop\n\
+             *.example.org {KEY}\n\
+             @revoked bad.example.com {KEY}\n\
+             {HASHED_EXAMPLE_PORT} {KEY}\n"
+        ));
+
+        assert_eq!(file.hash_all(), 2);
+        let lines = lines(&file);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "# comment");
+        assert_eq!(lines[3], format!("*.example.org {KEY}"));
+        assert_eq!(lines[4], format!("@revoked bad.example.com {KEY}"));
+        assert_eq!(lines[5], format!("{HASHED_EXAMPLE_PORT} {KEY}"));
+
+        let (example, address) = (&file.entries[0], &file.entries[1]);
+        assert!(matches!(example.hosts, HostPatterns::Hashed { .. }));
+        assert!(example.matches_host("example.com"));
+        assert!(address.matches_host("10.0.0.1"));
+        assert!(!address.matches_host("example.com"));
+        assert_eq!(example.comment, "laptop");
+        assert_eq!(example.public_key, address.public_key);
+
+        assert_eq!(file.hash_all(), 0);
+        let content = file.lines.join("\n") + "\n";
+        assert_eq!(parse(&content), file);
+    }
+}
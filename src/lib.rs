@@ -1,2 +1,12 @@
+pub mod agent;
 pub mod app;
+pub mod authorized_keys;
+pub mod backend;
 pub mod event;
+pub mod input;
+pub mod inventory;
+pub mod jobs;
+pub mod keys;
+pub mod known_hosts;
+pub mod ssh_config;
+pub mod ssh_files;
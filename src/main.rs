@@ -1,10 +1,16 @@
 use lazyssh::*;
+use ratatui::crossterm::event::{DisableBracketedPaste, EnableBracketedPaste};
+use ratatui::crossterm::execute;
+use std::io;
+use std::sync::Arc;
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
     let terminal = ratatui::init();
+    execute!(io::stdout(), EnableBracketedPaste)?;
     let event_handler = event::EventHandler::new();
-    let result = app::App::new(event_handler).run(terminal);
+    let result = app::App::new(event_handler, Arc::new(backend::RealBackend)).run(terminal);
+    execute!(io::stdout(), DisableBracketedPaste)?;
     ratatui::restore();
     result
 }
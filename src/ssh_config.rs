@@ -0,0 +1,1257 @@
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::{self, Filesystem};
+
+/// Maximum nesting of `Include` directives, same as OpenSSH.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Directives that may be given several times and accumulate instead of
+/// the first value winning.
+const MULTI_VALUE_KEYWORDS: &[&str] = &[
+    "certificatefile",
+    "dynamicforward",
+    "identityfile",
+    "localforward",
+    "remoteforward",
+    "sendenv",
+];
+
+/// Keys ssh tries when no `IdentityFile` is configured, in order.
+const DEFAULT_IDENTITY_FILES: &[&str] = &[
+    "~/.ssh/id_rsa",
+    "~/.ssh/id_ecdsa",
+    "~/.ssh/id_ecdsa_sk",
+    "~/.ssh/id_ed25519",
+    "~/.ssh/id_ed25519_sk",
+    "~/.ssh/id_xmss",
+    "~/.ssh/id_dsa",
+];
+
+/// Canonical spelling of common directives, used when displaying settings.
+const KNOWN_KEYWORDS: &[&str] = &[
+    "AddKeysToAgent",
+    "CertificateFile",
+    "Compression",
+    "ControlMaster",
+    "ControlPath",
+    "ControlPersist",
+    "DynamicForward",
+    "ForwardAgent",
+    "ForwardX11",
+    "HostKeyAlias",
+    "HostName",
+    "IdentitiesOnly",
+    "IdentityAgent",
+    "IdentityFile",
+    "LocalForward",
+    "LogLevel",
+    "Port",
+    "ProxyCommand",
+    "ProxyJump",
+    "RemoteForward",
+    "RequestTTY",
+    "SendEnv",
+    "ServerAliveCountMax",
+    "ServerAliveInterval",
+    "SetEnv",
+    "StrictHostKeyChecking",
+    "User",
+    "UserKnownHostsFile",
+];
+
+/// A single `Keyword value...` line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Directive {
+    /// Keyword as written in the file.
+    pub keyword: String,
+    /// Arguments, with quotes removed.
+    pub args: Vec<String>,
+    /// File the directive was read from.
+    pub source: PathBuf,
+    /// 1-based line number in `source`.
+    pub line: usize,
+}
+
+impl Directive {
+    /// Keyword in lower case, for comparisons.
+    pub fn key(&self) -> String {
+        self.keyword.to_ascii_lowercase()
+    }
+}
+
+/// A criterion of a `Match` line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchCriterion {
+    /// Criterion name in lower case, e.g. `host` or `exec`.
+    pub name: String,
+    /// Argument of the criterion, empty for `all`, `canonical` and `final`.
+    pub arg: String,
+    /// Whether the criterion was prefixed with `!`.
+    pub negated: bool,
+}
+
+/// What a block applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// Directives before the first `Host` or `Match` line.
+    Global,
+    /// A `Host` block with its patterns.
+    Host(Vec<String>),
+    /// A `Match` block with its criteria.
+    Match(Vec<MatchCriterion>),
+}
+
+/// A `Host` or `Match` block and the directives that follow it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    /// What the block applies to.
+    pub kind: BlockKind,
+    /// Directives in the block, in file order.
+    pub directives: Vec<Directive>,
+    /// File the block header was read from.
+    pub source: PathBuf,
+    /// 1-based line number of the block header, 0 for the global block.
+    pub line: usize,
+}
+
+/// A parsed `ssh_config` file with its includes expanded in place.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SshConfig {
+    /// Blocks in file order, starting with the global block. A block that
+    /// includes files opening blocks of their own is continued by a copy of
+    /// its header after them.
+    pub blocks: Vec<Block>,
+    /// Problems found while parsing, such as unreadable includes.
+    pub warnings: Vec<String>,
+}
+
+/// Effective settings for a host, as `ssh -G` would compute them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedHost {
+    /// Alias the settings were resolved for.
+    pub alias: String,
+    /// Settings in the order they were obtained, using canonical keyword spelling.
+    pub options: Vec<(String, String)>,
+    /// Where each block that applied to the host was defined.
+    pub sources: Vec<(PathBuf, usize)>,
+}
+
+impl ResolvedHost {
+    /// First value of a directive, compared case-insensitively.
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(keyword))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of a directive that may be given several times.
+    pub fn get_all(&self, keyword: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(keyword))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// `HostName`, defaulting to the alias itself.
+    pub fn hostname(&self) -> &str {
+        self.get("HostName").unwrap_or(&self.alias)
+    }
+
+    /// `User`, if set.
+    pub fn user(&self) -> Option<&str> {
+        self.get("User")
+    }
+
+    /// `Port`, defaulting to 22.
+    pub fn port(&self) -> u16 {
+        self.get("Port")
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(22)
+    }
+
+    /// Every `IdentityFile`, unexpanded.
+    pub fn identity_files(&self) -> Vec<&str> {
+        self.get_all("IdentityFile")
+    }
+
+    /// Every `IdentityFile` with `~` and `%` tokens expanded. Falls back to
+    /// ssh's default key names when none is configured.
+    pub fn expanded_identity_files(&self, fs: &dyn Filesystem) -> Vec<PathBuf> {
+        let configured = self.identity_files();
+        let files = if configured.is_empty() {
+            DEFAULT_IDENTITY_FILES.to_vec()
+        } else {
+            configured
+        };
+        files
+            .into_iter()
+            .map(|file| expand_tilde(fs, &self.expand_tokens(fs, file)))
+            .collect()
+    }
+
+    /// Expands the `%` tokens ssh accepts in paths such as `IdentityFile`.
+    pub fn expand_tokens(&self, fs: &dyn Filesystem, value: &str) -> String {
+        let home = fs.home_dir().unwrap_or_default();
+        let local_user = env::var("USER").unwrap_or_default();
+        let mut expanded = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('d') => expanded.push_str(&home.to_string_lossy()),
+                Some('h') => expanded.push_str(self.hostname()),
+                Some('n') => expanded.push_str(&self.alias),
+                Some('p') => expanded.push_str(&self.port().to_string()),
+                Some('r') => expanded.push_str(self.user().unwrap_or(&local_user)),
+                Some('u') => expanded.push_str(&local_user),
+                Some(other) => {
+                    expanded.push('%');
+                    expanded.push(other);
+                }
+                None => expanded.push('%'),
+            }
+        }
+        expanded
+    }
+
+    /// `ProxyJump`, if set.
+    pub fn proxy_jump(&self) -> Option<&str> {
+        self.get("ProxyJump")
+    }
+
+    /// Whether `ForwardAgent yes` applies.
+    pub fn forward_agent(&self) -> bool {
+        self.get("ForwardAgent")
+            .is_some_and(|value| value.eq_ignore_ascii_case("yes"))
+    }
+}
+
+impl SshConfig {
+    /// Reads the user configuration at `~/.ssh/config`.
+    pub fn load_user_config(fs: &dyn Filesystem) -> Self {
+        Self::load(fs, &fs.ssh_dir().join("config"))
+    }
+
+    /// Reads a configuration file, returning an empty configuration if it
+    /// does not exist.
+    pub fn load(fs: &dyn Filesystem, path: &Path) -> Self {
+        let mut config = SshConfig::default();
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        config.blocks.push(Block {
+            kind: BlockKind::Global,
+            directives: Vec::new(),
+            source: path.to_path_buf(),
+            line: 0,
+        });
+        if fs.exists(path) {
+            config.read_file(fs, path, &base_dir, 0);
+        }
+        config
+    }
+
+    /// Parses configuration text. `Include` paths are resolved relative to
+    /// `base_dir`.
+    pub fn parse(fs: &dyn Filesystem, content: &str, source: &Path, base_dir: &Path) -> Self {
+        let mut config = SshConfig::default();
+        config.blocks.push(Block {
+            kind: BlockKind::Global,
+            directives: Vec::new(),
+            source: source.to_path_buf(),
+            line: 0,
+        });
+        config.parse_content(fs, content, source, base_dir, 0);
+        config
+    }
+
+    fn read_file(&mut self, fs: &dyn Filesystem, path: &Path, base_dir: &Path, depth: usize) {
+        match fs.read_to_string(path) {
+            Ok(content) => self.parse_content(fs, &content, path, base_dir, depth),
+            Err(err) => self
+                .warnings
+                .push(format!("Failed to read {}: {}", path.display(), err)),
+        }
+    }
+
+    fn parse_content(
+        &mut self,
+        fs: &dyn Filesystem,
+        content: &str,
+        source: &Path,
+        base_dir: &Path,
+        depth: usize,
+    ) {
+        for (index, raw_line) in content.lines().enumerate() {
+            let line = index + 1;
+            let Some((keyword, args)) = parse_line(raw_line) else {
+                continue;
+            };
+
+            match keyword.to_ascii_lowercase().as_str() {
+                "host" => self.blocks.push(Block {
+                    kind: BlockKind::Host(args),
+                    directives: Vec::new(),
+                    source: source.to_path_buf(),
+                    line,
+                }),
+                "match" => self.blocks.push(Block {
+                    kind: BlockKind::Match(parse_match_criteria(&args)),
+                    directives: Vec::new(),
+                    source: source.to_path_buf(),
+                    line,
+                }),
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        self.warnings.push(format!(
+                            "{}:{}: Include nested too deeply",
+                            source.display(),
+                            line
+                        ));
+                        continue;
+                    }
+                    let block_count = self.blocks.len();
+                    for pattern in &args {
+                        for path in expand_include(fs, pattern, base_dir) {
+                            self.read_file(fs, &path, base_dir, depth + 1);
+                        }
+                    }
+                    // Blocks opened by the included files end with them, so
+                    // the directives that follow go on in the including block.
+                    if self.blocks.len() > block_count {
+                        let including = &self.blocks[block_count - 1];
+                        self.blocks.push(Block {
+                            kind: including.kind.clone(),
+                            directives: Vec::new(),
+                            source: including.source.clone(),
+                            line: including.line,
+                        });
+                    }
+                }
+                _ => {
+                    if let Some(block) = self.blocks.last_mut() {
+                        block.directives.push(Directive {
+                            keyword,
+                            args,
+                            source: source.to_path_buf(),
+                            line,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every concrete alias named on a `Host` line, in file order. Patterns
+    /// with wildcards or negations are skipped.
+    pub fn host_aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = Vec::new();
+        for block in &self.blocks {
+            if let BlockKind::Host(patterns) = &block.kind {
+                for pattern in patterns {
+                    if pattern.contains(['*', '?', '!']) {
+                        continue;
+                    }
+                    if !aliases.iter().any(|alias| alias == pattern) {
+                        aliases.push(pattern.clone());
+                    }
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Computes the effective settings for `alias`. For each directive the
+    /// first value obtained wins, except for those that accumulate.
+    pub fn resolve(&self, alias: &str) -> ResolvedHost {
+        let local_user = env::var("USER").unwrap_or_default();
+        let mut resolved = ResolvedHost {
+            alias: alias.to_string(),
+            options: Vec::new(),
+            sources: Vec::new(),
+        };
+
+        for block in &self.blocks {
+            let applies = match &block.kind {
+                BlockKind::Global => true,
+                BlockKind::Host(patterns) => match_pattern_list(alias, patterns),
+                BlockKind::Match(criteria) => criteria.iter().all(|criterion| {
+                    let hostname = resolved.hostname().to_string();
+                    let user = resolved.user().unwrap_or(&local_user).to_string();
+                    criterion.matches(alias, &hostname, &user, &local_user)
+                }),
+            };
+            if !applies {
+                continue;
+            }
+            let source = (block.source.clone(), block.line);
+            if block.line > 0 && !resolved.sources.contains(&source) {
+                resolved.sources.push(source);
+            }
+
+            for directive in &block.directives {
+                let key = directive.key();
+                let multi = MULTI_VALUE_KEYWORDS.contains(&key.as_str());
+                if !multi && resolved.get(&key).is_some() {
+                    continue;
+                }
+                resolved.options.push((
+                    canonical_keyword(&directive.keyword),
+                    directive.args.join(" "),
+                ));
+            }
+        }
+        resolved
+    }
+}
+
+impl MatchCriterion {
+    fn matches(&self, alias: &str, hostname: &str, user: &str, local_user: &str) -> bool {
+        let patterns: Vec<String> = self.arg.split(',').map(str::to_string).collect();
+        let result = match self.name.as_str() {
+            "all" | "final" => true,
+            "canonical" => false,
+            "host" => match_pattern_list(hostname, &patterns),
+            "originalhost" => match_pattern_list(alias, &patterns),
+            "user" => match_pattern_list(user, &patterns),
+            "localuser" => match_pattern_list(local_user, &patterns),
+            // `exec`, `localnetwork` and `tagged` depend on state we do not have.
+            _ => false,
+        };
+        result != self.negated
+    }
+}
+
+fn parse_match_criteria(args: &[String]) -> Vec<MatchCriterion> {
+    let mut criteria = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (negated, name) = match arg.strip_prefix('!') {
+            Some(name) => (true, name.to_ascii_lowercase()),
+            None => (false, arg.to_ascii_lowercase()),
+        };
+        let arg = match name.as_str() {
+            "all" | "canonical" | "final" => String::new(),
+            _ => args.next().cloned().unwrap_or_default(),
+        };
+        criteria.push(MatchCriterion { name, arg, negated });
+    }
+    criteria
+}
+
+/// Splits a configuration line into its keyword and arguments. Returns
+/// `None` for blank lines and comments.
+pub fn parse_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let keyword_end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = line[..keyword_end].to_string();
+    let rest = line[keyword_end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+    Some((keyword, split_args(rest)))
+}
+
+/// Splits arguments on whitespace, honouring double and single quotes. A
+/// token starting with `#` ends the line.
+pub fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) if c == '\\' && chars.peek().is_some_and(|n| *n == '"' || *n == '\'') => {
+                current.extend(chars.next());
+            }
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_token = true;
+            }
+            None if c.is_whitespace() => {
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            None if c == '#' && !in_token => break,
+            None => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        args.push(current);
+    }
+    args
+}
+
+/// Matches `text` against an ssh pattern with `*` and `?` wildcards,
+/// ignoring ASCII case.
+pub fn match_pattern(text: &str, pattern: &str) -> bool {
+    wildcard_match(&text.to_ascii_lowercase(), &pattern.to_ascii_lowercase())
+}
+
+fn wildcard_match(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `text` against a list of patterns. A matching negated pattern
+/// (`!pattern`) rejects the text even if another pattern matches.
+pub fn match_pattern_list(text: &str, patterns: &[String]) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if match_pattern(text, negated) => return false,
+            Some(_) => {}
+            None if match_pattern(text, pattern) => matched = true,
+            None => {}
+        }
+    }
+    matched
+}
+
+fn canonical_keyword(keyword: &str) -> String {
+    KNOWN_KEYWORDS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(keyword))
+        .map(|known| known.to_string())
+        .unwrap_or_else(|| keyword.to_string())
+}
+
+/// Expands `~/` to the home directory.
+pub fn expand_tilde(fs: &dyn Filesystem, path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => fs.home_dir().unwrap_or_default().join(rest),
+        None if path == "~" => fs.home_dir().unwrap_or_default(),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Resolves an `Include` argument to the files it names, sorted. Wildcards
+/// are supported in the last path component.
+fn expand_include(fs: &dyn Filesystem, pattern: &str, base_dir: &Path) -> Vec<PathBuf> {
+    let path = expand_tilde(fs, pattern);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        base_dir.join(path)
+    };
+
+    let file_pattern = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !file_pattern.contains(['*', '?']) {
+        return if fs.is_file(&path) {
+            vec![path]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut paths: Vec<PathBuf> = fs
+        .read_dir(&dir)
+        .map(|entries| {
+            entries
+                .into_iter()
+                .filter(|path| fs.is_file(path))
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| wildcard_match(name, &file_pattern))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+/// Directives edited through dedicated fields of the host form.
+const HOST_FORM_KEYWORDS: &[&str] = &["HostName", "User", "Port", "IdentityFile", "ProxyJump"];
+
+/// A `Host` entry as edited through the host form. Field values are
+/// unquoted and get quoted when written if they contain whitespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostEntry {
+    pub alias: String,
+    pub hostname: String,
+    pub user: String,
+    pub port: String,
+    pub identity_file: String,
+    pub proxy_jump: String,
+    /// Any other directives of the block, as `(keyword, value)` pairs.
+    pub extra_options: Vec<(String, String)>,
+}
+
+impl HostEntry {
+    /// Builds an entry from the directive lines of a `Host` block. `Include`
+    /// lines are left out, they stay in the block as they are.
+    fn from_lines<'a>(alias: &str, lines: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut entry = HostEntry {
+            alias: alias.to_string(),
+            ..HostEntry::default()
+        };
+        for (keyword, args) in lines.into_iter().filter_map(|line| parse_line(line)) {
+            let value = join_args(&args);
+            let field = match keyword.to_ascii_lowercase().as_str() {
+                "include" => continue,
+                "hostname" => &mut entry.hostname,
+                "user" => &mut entry.user,
+                "port" => &mut entry.port,
+                "identityfile" => &mut entry.identity_file,
+                "proxyjump" => &mut entry.proxy_jump,
+                _ => {
+                    entry.extra_options.push((keyword, value));
+                    continue;
+                }
+            };
+            // Fields hold a single unquoted value; anything else stays an extra option.
+            match args.as_slice() {
+                [arg] if field.is_empty() => *field = arg.clone(),
+                _ => entry.extra_options.push((keyword, value)),
+            }
+        }
+        entry
+    }
+
+    /// Directives the block should contain, in the order they are written
+    /// for a new block.
+    fn directives(&self) -> Vec<(String, String)> {
+        let fields = [
+            &self.hostname,
+            &self.user,
+            &self.port,
+            &self.identity_file,
+            &self.proxy_jump,
+        ];
+        let mut directives: Vec<(String, String)> = HOST_FORM_KEYWORDS
+            .iter()
+            .zip(fields)
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(keyword, value)| (keyword.to_string(), join_args(&[value.trim().to_string()])))
+            .collect();
+        directives.extend(self.extra_options.iter().cloned());
+        directives
+    }
+
+    /// Formats the extra options one `Keyword value` per line. Values may
+    /// contain any other character, e.g. `;` in a `ProxyCommand`.
+    pub fn extra_options_text(&self) -> String {
+        self.extra_options
+            .iter()
+            .map(|(keyword, value)| format!("{} {}", keyword, value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Parses extra options written one `Keyword value` per line.
+    pub fn parse_extra_options(text: &str) -> Result<Vec<(String, String)>, String> {
+        let mut options = Vec::new();
+        for line in text.lines() {
+            let Some((keyword, args)) = parse_line(line) else {
+                continue;
+            };
+            let lower = keyword.to_ascii_lowercase();
+            if matches!(lower.as_str(), "host" | "match" | "include") {
+                return Err(format!("{} cannot be used as an extra option", keyword));
+            }
+            if args.is_empty() {
+                return Err(format!("{} is missing a value", keyword));
+            }
+            options.push((canonical_keyword(&keyword), join_args(&args)));
+        }
+        Ok(options)
+    }
+}
+
+/// The raw lines of a configuration file, edited in place so comments,
+/// ordering and unrelated blocks survive a rewrite.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigDocument {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl ConfigDocument {
+    /// Reads a configuration file. A missing file yields an empty document.
+    pub fn load(fs: &dyn Filesystem, path: &Path) -> io::Result<Self> {
+        let content = match fs.read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            lines: content.lines().map(str::to_string).collect(),
+        })
+    }
+
+    /// Path the document is saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Document text, with a trailing newline.
+    pub fn content(&self) -> String {
+        let mut content = self.lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+
+    /// The entry of the `Host` block of `alias`, read from this file only,
+    /// not from the files it includes.
+    pub fn host_entry(&self, alias: &str) -> Result<Option<HostEntry>, String> {
+        let Some((start, end)) = self.find_host_block(alias)? else {
+            return Ok(None);
+        };
+        Ok(Some(HostEntry::from_lines(
+            alias,
+            &self.lines[start + 1..end],
+        )))
+    }
+
+    /// Adds a `Host` block, or updates the block of `original_alias` in place.
+    pub fn upsert_host(
+        &mut self,
+        original_alias: Option<&str>,
+        entry: &HostEntry,
+    ) -> Result<(), String> {
+        let Some(original_alias) = original_alias else {
+            self.insert_host(entry);
+            return Ok(());
+        };
+
+        let (start, end) = self.find_host_block(original_alias)?.ok_or_else(|| {
+            format!(
+                "Host {} not found in {}",
+                original_alias,
+                self.path.display()
+            )
+        })?;
+
+        if original_alias != entry.alias {
+            let indent = leading_whitespace(&self.lines[start]);
+            self.lines[start] = format!("{}Host {}", indent, entry.alias);
+        }
+
+        let indent = self.lines[start + 1..end]
+            .iter()
+            .find(|line| parse_line(line).is_some())
+            .map(|line| leading_whitespace(line))
+            .unwrap_or_else(|| "    ".to_string());
+        self.reconcile_directives(start, end, &entry.directives(), &indent);
+        Ok(())
+    }
+
+    /// Removes the `Host` block of `alias`.
+    pub fn remove_host(&mut self, alias: &str) -> Result<(), String> {
+        let (start, end) = self
+            .find_host_block(alias)?
+            .ok_or_else(|| format!("Host {} not found in {}", alias, self.path.display()))?;
+        // Comments directly above the block describe it, unless they open the file.
+        let comment_start = self.leading_comment_start(start);
+        let start = if comment_start > 0 && self.lines[comment_start - 1].trim().is_empty() {
+            comment_start
+        } else {
+            start
+        };
+        self.lines.drain(start..end);
+        // Drop the blank separator the block leaves behind.
+        if start > 0
+            && self
+                .lines
+                .get(start - 1)
+                .is_some_and(|line| line.trim().is_empty())
+            && self
+                .lines
+                .get(start)
+                .is_none_or(|line| line.trim().is_empty())
+        {
+            self.lines.remove(start - 1);
+        }
+        Ok(())
+    }
+
+    /// Writes the document through a temporary file, keeping the previous
+    /// version as `config.bak`. Returns the backup path, if a backup was made.
+    pub fn save(&self, fs: &dyn Filesystem) -> io::Result<Option<PathBuf>> {
+        let backup = if fs.exists(&self.path) {
+            let mut backup = self.path.as_os_str().to_owned();
+            backup.push(".bak");
+            let backup = PathBuf::from(backup);
+            fs.copy(&self.path, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        backend::replace_file(fs, &self.path, self.content().as_bytes(), 0o600)?;
+        Ok(backup)
+    }
+
+    fn insert_host(&mut self, entry: &HostEntry) {
+        let mut block = vec![format!("Host {}", entry.alias)];
+        block.extend(
+            entry
+                .directives()
+                .into_iter()
+                .map(|(keyword, value)| format!("    {} {}", keyword, value)),
+        );
+
+        // Settings are first-match-wins, so a new host has to go before a
+        // catch-all `Host *` block to take effect.
+        let catch_all = self.block_headers().into_iter().find(|&index| {
+            parse_line(&self.lines[index]).is_some_and(|(keyword, args)| {
+                keyword.eq_ignore_ascii_case("host") && args == ["*"]
+            })
+        });
+
+        match catch_all {
+            Some(index) => {
+                let index = self.leading_comment_start(index);
+                block.push(String::new());
+                self.lines.splice(index..index, block);
+            }
+            None => {
+                if self
+                    .lines
+                    .last()
+                    .is_some_and(|line| !line.trim().is_empty())
+                {
+                    self.lines.push(String::new());
+                }
+                self.lines.extend(block);
+            }
+        }
+    }
+
+    /// Updates the directive lines between `start` (the header) and `end` to
+    /// match `directives`, keyword by keyword, keeping comments and the
+    /// position of lines that stay.
+    fn reconcile_directives(
+        &mut self,
+        start: usize,
+        end: usize,
+        directives: &[(String, String)],
+        indent: &str,
+    ) {
+        let mut block: Vec<Option<String>> =
+            self.lines[start..end].iter().cloned().map(Some).collect();
+        let mut appended = Vec::new();
+
+        let mut keywords: Vec<String> = directives
+            .iter()
+            .map(|(keyword, _)| keyword.to_ascii_lowercase())
+            .collect();
+        for line in block.iter().skip(1).flatten() {
+            if let Some((keyword, _)) = parse_line(line) {
+                keywords.push(keyword.to_ascii_lowercase());
+            }
+        }
+        let mut seen = Vec::new();
+        for keyword in keywords {
+            // Includes are not part of the entry and are kept as they are.
+            if keyword == "include" || seen.contains(&keyword) {
+                continue;
+            }
+            seen.push(keyword.clone());
+
+            let existing: Vec<usize> = (1..block.len())
+                .filter(|&i| {
+                    block[i]
+                        .as_deref()
+                        .and_then(parse_line)
+                        .is_some_and(|(k, _)| k.eq_ignore_ascii_case(&keyword))
+                })
+                .collect();
+            let wanted: Vec<&(String, String)> = directives
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case(&keyword))
+                .collect();
+
+            for (i, index) in existing.iter().enumerate() {
+                match wanted.get(i) {
+                    Some((_, value)) => {
+                        let line = block[*index].as_deref().unwrap_or_default();
+                        let (written, args) = parse_line(line).unwrap_or_default();
+                        if join_args(&args) != *value {
+                            block[*index] =
+                                Some(format!("{}{} {}", leading_whitespace(line), written, value));
+                        }
+                    }
+                    None => block[*index] = None,
+                }
+            }
+            for (keyword, value) in wanted.iter().skip(existing.len()) {
+                appended.push(format!("{}{} {}", indent, keyword, value));
+            }
+        }
+
+        let mut block: Vec<String> = block.into_iter().flatten().collect();
+        let insert_at = block
+            .iter()
+            .rposition(|line| parse_line(line).is_some())
+            .map(|index| index + 1)
+            .unwrap_or(1);
+        block.splice(insert_at..insert_at, appended);
+        self.lines.splice(start..end, block);
+    }
+
+    fn block_headers(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                parse_line(line).is_some_and(|(keyword, _)| {
+                    keyword.eq_ignore_ascii_case("host") || keyword.eq_ignore_ascii_case("match")
+                })
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Start of the comment lines directly above `index`, which describe
+    /// the block starting there.
+    fn leading_comment_start(&self, index: usize) -> usize {
+        let mut start = index;
+        while start > 0 && self.lines[start - 1].trim_start().starts_with('#') {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Line range of the `Host` block whose only pattern is `alias`. Trailing
+    /// blank lines and comments are left to the next block.
+    fn find_host_block(&self, alias: &str) -> Result<Option<(usize, usize)>, String> {
+        let headers = self.block_headers();
+        for (position, &start) in headers.iter().enumerate() {
+            let Some((keyword, patterns)) = parse_line(&self.lines[start]) else {
+                continue;
+            };
+            if !keyword.eq_ignore_ascii_case("host") || !patterns.iter().any(|p| p == alias) {
+                continue;
+            }
+            if patterns.len() > 1 {
+                return Err(format!(
+                    "Host {} shares its block with other patterns ({}), edit it by hand",
+                    alias,
+                    patterns.join(" ")
+                ));
+            }
+
+            let mut end = headers
+                .get(position + 1)
+                .map(|&next| self.leading_comment_start(next))
+                .unwrap_or(self.lines.len());
+            while end > start + 1 && self.lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            return Ok(Some((start, end)));
+        }
+        Ok(None)
+    }
+}
+
+/// Joins arguments back into a value, quoting those that contain
+/// whitespace so [`split_args`] reads them back the same.
+fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg.replace('"', "\\\""))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn leading_whitespace(line: &str) -> String {
+    line.chars().take_while(|c| c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FakeBackend;
+
+    fn load(backend: &FakeBackend) -> SshConfig {
+        SshConfig::load_user_config(backend)
+    }
+
+    #[test]
+    fn first_value_wins_except_for_identity_files() {
+        let backend = FakeBackend::new().with_ssh_file(
+            "config",
+            "\
+User everyone
+
+Host web
+    User alice
+    IdentityFile ~/.ssh/id_web
+
+Host *
+    User root
+    Port 2222
+    IdentityFile ~/.ssh/id_default
+",
+        );
+        let config = load(&backend);
+
+        let web = config.resolve("web");
+        assert_eq!(web.user(), Some("everyone"));
+        assert_eq!(web.port(), 2222);
+        assert_eq!(web.hostname(), "web");
+        assert_eq!(web.identity_files(), ["~/.ssh/id_web", "~/.ssh/id_default"]);
+        let config_path = backend.ssh_dir().join("config");
+        assert_eq!(web.sources, [(config_path.clone(), 3), (config_path, 7)]);
+
+        assert_eq!(config.host_aliases(), ["web"]);
+    }
+
+    #[test]
+    fn match_blocks_see_the_settings_obtained_so_far() {
+        let backend = FakeBackend::new().with_ssh_file(
+            "config",
+            "\
+Host db
+    HostName db.example.com
+
+Match host *.example.com !originalhost web
+    ForwardAgent yes
+
+Match originalhost web
+    Port 2200
+
+Match exec \"true\"
+    User nobody
+
+Match all
+    User fallback
+",
+        );
+        let config = load(&backend);
+
+        let db = config.resolve("db");
+        assert!(db.forward_agent());
+        assert_eq!(db.port(), 22);
+        assert_eq!(db.user(), Some("fallback"));
+
+        let web = config.resolve("web.example.com");
+        assert!(web.forward_agent());
+
+        let web = config.resolve("web");
+        assert!(!web.forward_agent());
+        assert_eq!(web.port(), 2200);
+    }
+
+    #[test]
+    fn include_reads_matching_files_in_order_and_returns_to_the_including_block() {
+        let backend = FakeBackend::new()
+            .with_ssh_file(
+                "config",
+                "\
+Include conf.d/*.conf
+
+Host web
+    HostName web.example.com
+    Include extra
+    User alice
+",
+            )
+            .with_ssh_file("conf.d/b.conf", "Host b\n    HostName b.example.com\n")
+            .with_ssh_file("conf.d/a.conf", "Host a\n    HostName a.example.com\n")
+            .with_ssh_file("conf.d/notes.txt", "Host ignored\n")
+            .with_ssh_file("extra", "Host other\n    User bob\n");
+        let config = load(&backend);
+
+        assert!(config.warnings.is_empty());
+        assert_eq!(config.host_aliases(), ["a", "b", "web", "other"]);
+        assert_eq!(config.resolve("a").hostname(), "a.example.com");
+
+        let web = config.resolve("web");
+        assert_eq!(web.hostname(), "web.example.com");
+        assert_eq!(web.user(), Some("alice"));
+        assert_eq!(web.sources, [(backend.ssh_dir().join("config"), 3)]);
+
+        let other = config.resolve("other");
+        assert_eq!(other.user(), Some("bob"));
+    }
+
+    fn document(content: &str) -> ConfigDocument {
+        let backend = FakeBackend::new().with_ssh_file("config", content);
+        ConfigDocument::load(&backend, &backend.ssh_dir().join("config")).unwrap()
+    }
+
+    #[test]
+    fn host_entry_comes_from_the_block_lines_and_keeps_includes() {
+        let mut document = document(
+            "\
+Host web
+    HostName web.example.com
+    Include web.d/*
+    User alice
+    LocalCommand echo \"a  b\"
+",
+        );
+        let mut entry = document.host_entry("web").unwrap().unwrap();
+        assert_eq!(entry.hostname, "web.example.com");
+        assert_eq!(entry.user, "alice");
+        assert_eq!(
+            entry.extra_options,
+            [("LocalCommand".to_string(), "echo \"a  b\"".to_string())]
+        );
+
+        entry.user = "bob".to_string();
+        document.upsert_host(Some("web"), &entry).unwrap();
+        assert_eq!(
+            document.content(),
+            "\
+Host web
+    HostName web.example.com
+    Include web.d/*
+    User bob
+    LocalCommand echo \"a  b\"
+"
+        );
+        assert_eq!(document.host_entry("db"), Ok(None));
+    }
+
+    #[test]
+    fn values_with_spaces_are_quoted_and_read_back_unquoted() {
+        let backend = FakeBackend::new().with_ssh_file("config", "Host old\n");
+        let path = backend.ssh_dir().join("config");
+        let mut document = ConfigDocument::load(&backend, &path).unwrap();
+        let entry = HostEntry {
+            alias: "web".to_string(),
+            hostname: "web.example.com".to_string(),
+            identity_file: "~/.ssh/my key".to_string(),
+            ..HostEntry::default()
+        };
+        document.upsert_host(None, &entry).unwrap();
+        assert_eq!(
+            document.save(&backend).unwrap(),
+            Some(path.with_file_name("config.bak"))
+        );
+        document.save(&backend).unwrap();
+
+        assert_eq!(
+            backend.ssh_file("config").unwrap(),
+            "Host old\n\nHost web\n    HostName web.example.com\n    IdentityFile \"~/.ssh/my key\"\n"
+        );
+        assert_eq!(
+            backend.ssh_file("config.bak").unwrap(),
+            backend.ssh_file("config").unwrap()
+        );
+        assert_eq!(backend.file(&path).unwrap().mode, 0o600);
+        assert!(backend.ssh_file("config.tmp").is_none());
+
+        let document = ConfigDocument::load(&backend, &path).unwrap();
+        assert_eq!(document.host_entry("web"), Ok(Some(entry)));
+        let config = SshConfig::load_user_config(&backend);
+        assert_eq!(config.resolve("web").identity_files(), ["~/.ssh/my key"]);
+    }
+
+    #[test]
+    fn extra_options_are_one_per_line() {
+        let options = HostEntry::parse_extra_options(
+            "ProxyCommand ssh -W %h:%p jump; true\n\n  SetEnv GREETING=\"hello world\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            [
+                (
+                    "ProxyCommand".to_string(),
+                    "ssh -W %h:%p jump; true".to_string()
+                ),
+                ("SetEnv".to_string(), "\"GREETING=hello world\"".to_string()),
+            ]
+        );
+        let entry = HostEntry {
+            extra_options: options.clone(),
+            ..HostEntry::default()
+        };
+        assert_eq!(
+            HostEntry::parse_extra_options(&entry.extra_options_text()),
+            Ok(options)
+        );
+
+        assert!(HostEntry::parse_extra_options("Include other").is_err());
+        assert!(HostEntry::parse_extra_options("ServerAliveInterval").is_err());
+    }
+
+    #[test]
+    fn expands_tilde_and_percent_tokens_against_the_filesystem_home() {
+        let backend = FakeBackend::new().with_ssh_file(
+            "config",
+            "\
+Host web
+    HostName web.example.com
+    User alice
+    Port 2222
+    IdentityFile ~/.ssh/%h_%r_%p
+    IdentityFile %d/keys/%n%%
+",
+        );
+        let config = load(&backend);
+
+        assert_eq!(
+            config.resolve("web").expanded_identity_files(&backend),
+            [
+                PathBuf::from("/home/user/.ssh/web.example.com_alice_2222"),
+                PathBuf::from("/home/user/keys/web%"),
+            ]
+        );
+        assert_eq!(
+            config.resolve("db").expanded_identity_files(&backend)[0],
+            PathBuf::from("/home/user/.ssh/id_rsa")
+        );
+        assert_eq!(expand_tilde(&backend, "~"), PathBuf::from("/home/user"));
+        assert_eq!(
+            expand_tilde(&backend, "/etc/ssh"),
+            PathBuf::from("/etc/ssh")
+        );
+    }
+}